@@ -1,27 +1,141 @@
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
-    fs,
+    collections::{HashMap, VecDeque},
+    env, fmt, fs,
+    time::Instant,
 };
+use xorshift::Rng;
 
-struct NoCircularPathError;
-struct WrongCharError;
+const NORTH: u8 = 1;
+const SOUTH: u8 = 2;
+const WEST: u8 = 4;
+const EAST: u8 = 8;
+const DIRECTIONS: [(u8, i32, i32); 4] =
+    [(NORTH, 0, -1), (SOUTH, 0, 1), (WEST, -1, 0), (EAST, 1, 0)];
+const PIPES: [char; 6] = ['|', '-', 'L', 'J', '7', 'F'];
 
-#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+#[derive(Debug)]
+enum PipeError {
+    UnknownChar {
+        row: usize,
+        col: usize,
+        c: char,
+    },
+    RaggedRow {
+        row: usize,
+        len: usize,
+        expected: usize,
+    },
+    NoStart,
+    MultipleStarts(Pos, Pos),
+    StartNotConnected(Pos),
+    DeadEnd(Pos),
+    LeavesMap(Pos),
+}
+
+impl fmt::Display for PipeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipeError::UnknownChar { row, col, c } => {
+                write!(
+                    f,
+                    "unknown character '{}' at row {}, col {}",
+                    c,
+                    row + 1,
+                    col + 1
+                )
+            }
+            PipeError::RaggedRow { row, len, expected } => {
+                write!(
+                    f,
+                    "row {} has length {}, expected {}",
+                    row + 1,
+                    len,
+                    expected
+                )
+            }
+            PipeError::NoStart => write!(f, "no start tile 'S'"),
+            PipeError::MultipleStarts(a, b) => {
+                write!(f, "more than one start tile, at {} and {}", a, b)
+            }
+            PipeError::StartNotConnected(pos) => {
+                write!(
+                    f,
+                    "fewer than two pipes connect to the start tile at {}",
+                    pos
+                )
+            }
+            PipeError::DeadEnd(pos) => write!(f, "dead end at {}", pos),
+            PipeError::LeavesMap(pos) => write!(f, "pipe at {} leads off the map", pos),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
 struct Pos {
     x: i32,
     y: i32,
 }
 
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "row {}, col {}", self.y + 1, self.x + 1)
+    }
+}
+
+// Connection flags for each tile, stored row by row.
 struct Diagram {
-    map: HashMap<Pos, Vec<Pos>>,
+    grid: Vec<u8>,
+    width: i32,
+    height: i32,
     start_pos: Pos,
-    max_x: i32,
-    max_y: i32,
+    start_pipe: Option<char>,
+}
+
+impl Diagram {
+    fn index(&self, pos: Pos) -> Option<usize> {
+        (pos.x >= 0 && pos.x < self.width && pos.y >= 0 && pos.y < self.height)
+            .then(|| (pos.y * self.width + pos.x) as usize)
+    }
+
+    fn flags(&self, pos: Pos) -> u8 {
+        self.index(pos).map_or(0, |i| self.grid[i])
+    }
+
+    // Directions the pipe at `pos` leads in, with the tile each one reaches.
+    // The tile may be off the map.
+    fn neighbours(&self, pos: Pos) -> impl Iterator<Item = (u8, Pos)> {
+        let flags = self.flags(pos);
+        DIRECTIONS
+            .into_iter()
+            .filter(move |(dir, _, _)| flags & dir != 0)
+            .map(move |(dir, dx, dy)| {
+                (
+                    dir,
+                    Pos {
+                        x: pos.x + dx,
+                        y: pos.y + dy,
+                    },
+                )
+            })
+    }
+
+    fn connects_back(&self, next: Pos, dir: u8) -> bool {
+        self.flags(next) & opposite(dir) != 0
+    }
 }
 
-fn try_path(d: &mut Diagram) -> Result<(i32, Vec<Pos>), NoCircularPathError> {
+fn opposite(dir: u8) -> u8 {
+    match dir {
+        NORTH => SOUTH,
+        SOUTH => NORTH,
+        WEST => EAST,
+        _ => WEST,
+    }
+}
+
+fn try_path(d: &mut Diagram) -> Result<(i32, Vec<Pos>), PipeError> {
     let mut cur_pos: Pos = d.start_pos;
-    let mut last_pos: Pos = cur_pos;
+    let mut came_from: u8 = 0;
     let mut steps: i32 = 0;
     let mut path: Vec<Pos> = vec![];
     'move_loop: loop {
@@ -30,222 +144,666 @@ fn try_path(d: &mut Diagram) -> Result<(i32, Vec<Pos>), NoCircularPathError> {
             return Ok((steps, path));
         }
 
-        for option in d.map.get(&cur_pos).expect("Pos should be mapped") {
-            if option.x < 0 || option.x > d.max_x || option.y < 0 || option.y > d.max_y {
-                return Err(NoCircularPathError);
+        for (dir, option) in d.neighbours(cur_pos) {
+            if d.index(option).is_none() {
+                return Err(PipeError::LeavesMap(cur_pos));
             }
-            if *option != last_pos && d.map.get(option).unwrap().contains(&cur_pos) {
-                last_pos = cur_pos;
-                cur_pos = *option;
+            if dir != came_from && d.connects_back(option, dir) {
+                came_from = opposite(dir);
+                cur_pos = option;
                 steps += 1;
                 continue 'move_loop;
             }
         }
+        return Err(PipeError::DeadEnd(cur_pos));
     }
 }
 
-fn try_paths(d: &mut Diagram) -> Result<(i32, Vec<Pos>), NoCircularPathError> {
-    for c in ['|', '-', 'L', 'J', '7', 'F'] {
-        let mut targets: Vec<Pos> = vec![];
-        match c {
-            '|' | '-' | 'L' | 'J' | '7' | 'F' => {
-                if let Ok(v) = get_offset(c, d.start_pos.x, d.start_pos.y) {
-                    targets.extend_from_slice(&v);
-                }
-            }
-            _ => (),
-        }
-        d.map.insert(d.start_pos, targets);
+// Pipe shapes for the start tile whose ends both lead into a pipe that
+// connects back to it.
+fn start_pipe_candidates(d: &Diagram) -> Vec<char> {
+    PIPES
+        .into_iter()
+        .filter(|&c| {
+            DIRECTIONS
+                .iter()
+                .filter(|(dir, _, _)| pipe_flags(c).unwrap() & dir != 0)
+                .all(|&(dir, dx, dy)| {
+                    let end = Pos {
+                        x: d.start_pos.x + dx,
+                        y: d.start_pos.y + dy,
+                    };
+                    d.connects_back(end, dir)
+                })
+        })
+        .collect()
+}
+
+fn try_paths(d: &mut Diagram) -> Result<(i32, Vec<Pos>), PipeError> {
+    let mut error = PipeError::StartNotConnected(d.start_pos);
+    let start = d.index(d.start_pos).expect("Start should be on the map");
+    for c in start_pipe_candidates(d) {
+        d.grid[start] = pipe_flags(c).unwrap();
         match try_path(d) {
-            Ok(v) => return Ok(v),
-            Err(_) => (),
+            Ok(v) => {
+                d.start_pipe = Some(c);
+                return Ok(v);
+            }
+            Err(e) => error = e,
         }
     }
-    Err(NoCircularPathError)
+    Err(error)
 }
 
-fn expand_path_by_2x(path: &Vec<Pos>) -> HashSet<Pos> {
-    let mut pipes: HashSet<Pos> = HashSet::new();
-    for i in 0..(path.len() - 1) {
-        let new_p1 = Pos {
-            x: path[i].x * 2,
-            y: path[i].y * 2,
-        };
-        let new_p2 = Pos {
-            x: path[i + 1].x * 2,
-            y: path[i + 1].y * 2,
-        };
-        let new_p3 = Pos {
-            x: (new_p1.x + new_p2.x) / 2,
-            y: (new_p1.y + new_p2.y) / 2,
-        };
-        pipes.insert(new_p1);
-        pipes.insert(new_p3);
-    }
-    pipes
-}
-
-fn get_area(path: &Vec<Pos>, max_x: i32, max_y: i32) -> i32 {
-    let mut larger_path: HashSet<Pos> = expand_path_by_2x(path);
-    let mut queue: VecDeque<Pos> = VecDeque::new();
-    queue.push_back(Pos { x: -1, y: -1 });
-    larger_path.insert(Pos { x: -1, y: -1 });
-
-    while queue.len() > 0 {
-        let pos = queue.pop_front().expect("Queue should have a pos");
-
-        for offset in [
-            Pos { x: 0, y: -1 },
-            Pos { x: 1, y: 0 },
-            Pos { x: 0, y: 1 },
-            Pos { x: -1, y: 0 },
-        ] {
-            let new_pos = Pos {
-                x: pos.x + offset.x,
-                y: pos.y + offset.y,
-            };
-            if new_pos.x < -1
-                || new_pos.x > 2 * max_x + 1
-                || new_pos.y < -1
-                || new_pos.y > max_y * 2 + 1
-            {
+// Breadth-first search from the start tile, only stepping between pipes that
+// connect to each other. Gives the distance to every tile on the main loop.
+fn get_loop_distances(d: &Diagram) -> Vec<Option<i32>> {
+    let mut distances = vec![None; d.grid.len()];
+    let start = d.index(d.start_pos).expect("Start should be on the map");
+    distances[start] = Some(0);
+    let mut queue = VecDeque::from([(d.start_pos, 0)]);
+
+    while let Some((pos, dist)) = queue.pop_front() {
+        for (dir, next) in d.neighbours(pos) {
+            let Some(i) = d.index(next) else { continue };
+            if distances[i].is_some() || !d.connects_back(next, dir) {
                 continue;
             }
-            if !larger_path.contains(&new_pos) {
-                larger_path.insert(new_pos);
-                queue.push_back(new_pos);
+            distances[i] = Some(dist + 1);
+            queue.push_back((next, dist + 1));
+        }
+    }
+    distances
+}
+
+fn render_distances(d: &Diagram, distances: &[Option<i32>]) -> String {
+    distances
+        .chunks(d.width as usize)
+        .map(|row| {
+            row.iter()
+                .map(|dist| match dist {
+                    Some(dist) => char::from_digit((dist % 10) as u32, 10).unwrap(),
+                    None => '.',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn loop_mask(d: &Diagram, path: &[Pos]) -> Vec<bool> {
+    let mut on_loop = vec![false; d.grid.len()];
+    for pos in path {
+        on_loop[d.index(*pos).expect("Path should stay on the map")] = true;
+    }
+    on_loop
+}
+
+// Flood fills the outside of the loop on a grid of twice the resolution, so
+// the fill can squeeze between pipes that touch without connecting. The
+// doubled grid has a one tile border so the fill can get all the way around.
+fn get_enclosed(d: &Diagram, path: &[Pos]) -> Vec<bool> {
+    let (width, height) = (2 * d.width as usize + 1, 2 * d.height as usize + 1);
+    let to_index = |x: i32, y: i32| (y + 1) as usize * width + (x + 1) as usize;
+    let mut blocked = vec![false; width * height];
+    for w in path.windows(2) {
+        blocked[to_index(w[0].x * 2, w[0].y * 2)] = true;
+        blocked[to_index(w[0].x + w[1].x, w[0].y + w[1].y)] = true;
+    }
+
+    let mut queue: VecDeque<usize> = VecDeque::from([0]);
+    blocked[0] = true;
+    while let Some(i) = queue.pop_front() {
+        let (x, y) = (i % width, i / width);
+        let mut neighbours = vec![];
+        if y > 0 {
+            neighbours.push(i - width);
+        }
+        if y + 1 < height {
+            neighbours.push(i + width);
+        }
+        if x > 0 {
+            neighbours.push(i - 1);
+        }
+        if x + 1 < width {
+            neighbours.push(i + 1);
+        }
+        for next in neighbours {
+            if !blocked[next] {
+                blocked[next] = true;
+                queue.push_back(next);
             }
         }
     }
 
-    let mut area = 0;
-    for x in 0..(max_x * 2 + 1) {
-        for y in 0..(max_y * 2 + 1) {
-            if x % 2 == 0 && y % 2 == 0 && !larger_path.contains(&Pos { x, y }) {
-                area += 1;
+    (0..d.height)
+        .flat_map(|y| (0..d.width).map(move |x| (x, y)))
+        .map(|(x, y)| !blocked[to_index(x * 2, y * 2)])
+        .collect()
+}
+
+// Shoelace formula for the area of the loop polygon, then Pick's theorem
+// (A = i + b/2 - 1) for the number of tiles strictly inside it.
+fn get_area_shoelace(path: &[Pos]) -> i32 {
+    let double_area: i64 = path
+        .windows(2)
+        .map(|w| w[0].x as i64 * w[1].y as i64 - w[1].x as i64 * w[0].y as i64)
+        .sum();
+    let boundary = path.len() as i64 - 1;
+    (double_area.abs() / 2 - boundary / 2 + 1) as i32
+}
+
+// Scans each row left to right, flipping between outside and inside on every
+// loop tile with a pipe going north ('|', 'L' and 'J').
+fn get_enclosed_scanline(d: &Diagram, path: &[Pos]) -> Vec<bool> {
+    let on_loop = loop_mask(d, path);
+    let mut enclosed = vec![false; d.grid.len()];
+    let width = d.width as usize;
+    for ((flags, on_loop), enclosed) in d
+        .grid
+        .chunks(width)
+        .zip(on_loop.chunks(width))
+        .zip(enclosed.chunks_mut(width))
+    {
+        let mut inside = false;
+        for ((f, on_loop), enclosed) in flags.iter().zip(on_loop).zip(enclosed) {
+            if *on_loop {
+                if f & NORTH != 0 {
+                    inside = !inside;
+                }
+            } else {
+                *enclosed = inside;
             }
         }
     }
-    area
+    enclosed
+}
+
+fn get_area_scanline(d: &Diagram, path: &[Pos]) -> i32 {
+    get_enclosed_scanline(d, path)
+        .iter()
+        .filter(|e| **e)
+        .count() as i32
+}
+
+// Draws the main loop with box-drawing characters. Every other tile, junk
+// pipes included, is shown as 'I' when enclosed by the loop and 'O' otherwise.
+fn render(d: &Diagram, path: &[Pos], enclosed: &[bool]) -> String {
+    let on_loop = loop_mask(d, path);
+    (0..d.grid.len())
+        .map(|i| {
+            if on_loop[i] {
+                match d.grid[i] {
+                    f if f == NORTH | SOUTH => '│',
+                    f if f == WEST | EAST => '─',
+                    f if f == NORTH | EAST => '└',
+                    f if f == NORTH | WEST => '┘',
+                    f if f == SOUTH | WEST => '┐',
+                    f if f == SOUTH | EAST => '┌',
+                    _ => '?',
+                }
+            } else if enclosed[i] {
+                'I'
+            } else {
+                'O'
+            }
+        })
+        .collect::<Vec<char>>()
+        .chunks(d.width as usize)
+        .map(|row| row.iter().collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n")
 }
 
-fn get_offset(c: char, x: i32, y: i32) -> Result<[Pos; 2], WrongCharError> {
+fn pipe_flags(c: char) -> Option<u8> {
     match c {
-        '|' => Ok([
-            Pos {
-                x: x as i32,
-                y: y as i32 - 1,
-            },
-            Pos {
-                x: x as i32,
-                y: y as i32 + 1,
-            },
-        ]),
-        '-' => Ok([
-            Pos {
-                x: x as i32 - 1,
-                y: y as i32,
-            },
-            Pos {
-                x: x as i32 + 1,
-                y: y as i32,
-            },
-        ]),
-        'L' => Ok([
-            Pos {
-                x: x as i32,
-                y: y as i32 - 1,
-            },
-            Pos {
-                x: x as i32 + 1,
-                y: y as i32,
-            },
-        ]),
-        'J' => Ok([
-            Pos {
-                x: x as i32,
-                y: y as i32 - 1,
-            },
-            Pos {
-                x: x as i32 - 1,
-                y: y as i32,
-            },
-        ]),
-        '7' => Ok([
-            Pos {
-                x: x as i32 - 1,
-                y: y as i32,
-            },
-            Pos {
-                x: x as i32,
-                y: y as i32 + 1,
-            },
-        ]),
-        'F' => Ok([
-            Pos {
-                x: x as i32 + 1,
-                y: y as i32,
-            },
-            Pos {
-                x: x as i32,
-                y: y as i32 + 1,
-            },
-        ]),
-        _ => Err(WrongCharError),
+        '|' => Some(NORTH | SOUTH),
+        '-' => Some(WEST | EAST),
+        'L' => Some(NORTH | EAST),
+        'J' => Some(NORTH | WEST),
+        '7' => Some(SOUTH | WEST),
+        'F' => Some(SOUTH | EAST),
+        _ => None,
     }
 }
 
-fn main() {
-    let mut diagram_map: HashMap<Pos, Vec<Pos>> = HashMap::new();
-    let mut start_pos = Pos { x: -1, y: -1 };
-    let (mut max_x, mut max_y) = (0, 0);
-
-    for (y, line) in fs::read_to_string("input.txt")
-        .expect("File should exist")
-        .lines()
-        .enumerate()
-    {
+fn parse_diagram(input: &str) -> Result<Diagram, PipeError> {
+    let mut grid: Vec<u8> = vec![];
+    let mut start_pos: Option<Pos> = None;
+    let (mut width, mut height) = (0, 0);
+
+    for (y, line) in input.trim_end().lines().enumerate() {
+        let len = line.chars().count();
+        if y == 0 {
+            width = len as i32;
+        } else if len != width as usize {
+            return Err(PipeError::RaggedRow {
+                row: y,
+                len,
+                expected: width as usize,
+            });
+        }
         for (x, c) in line.chars().enumerate() {
-            let mut targets: Vec<Pos> = vec![];
+            let pos = Pos {
+                x: x as i32,
+                y: y as i32,
+            };
             match c {
-                '|' | '-' | 'L' | 'J' | '7' | 'F' => {
-                    if let Ok(v) = get_offset(c, x as i32, y as i32) {
-                        targets.extend_from_slice(&v);
+                '|' | '-' | 'L' | 'J' | '7' | 'F' => grid.push(pipe_flags(c).unwrap()),
+                'S' => {
+                    if let Some(first) = start_pos {
+                        return Err(PipeError::MultipleStarts(first, pos));
                     }
+                    start_pos = Some(pos);
+                    grid.push(0);
                 }
-                'S' => {
-                    start_pos = Pos {
-                        x: x as i32,
-                        y: y as i32,
-                    };
+                '.' => grid.push(0),
+                _ => return Err(PipeError::UnknownChar { row: y, col: x, c }),
+            }
+        }
+        height = y as i32 + 1;
+    }
+
+    Ok(Diagram {
+        grid,
+        width,
+        height,
+        start_pos: start_pos.ok_or(PipeError::NoStart)?,
+        start_pipe: None,
+    })
+}
+
+// Draws a closed path as pipe characters, with 'S' on its first tile.
+fn draw_loop(width: usize, height: usize, path: &[Pos]) -> String {
+    let mut map = vec![vec!['.'; width]; height];
+    for (i, pos) in path.iter().enumerate() {
+        let prev = path[(i + path.len() - 1) % path.len()];
+        let next = path[(i + 1) % path.len()];
+        let flags: u8 = DIRECTIONS
+            .iter()
+            .filter(|(_, dx, dy)| {
+                [prev, next]
+                    .iter()
+                    .any(|p| p.x == pos.x + dx && p.y == pos.y + dy)
+            })
+            .map(|(dir, _, _)| dir)
+            .sum();
+        map[pos.y as usize][pos.x as usize] = if i == 0 {
+            'S'
+        } else {
+            PIPES
+                .into_iter()
+                .find(|&c| pipe_flags(c) == Some(flags))
+                .expect("Path should only turn by right angles")
+        };
+    }
+    map.iter()
+        .map(|row| row.iter().collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// A comb shaped loop filling a `size` x `size` map: up the first column, down
+// and up through the rest, and back along the bottom row.
+fn comb_maze(size: i32) -> String {
+    let mut path = vec![];
+    for x in 0..size {
+        let bottom = if x == 0 || x == size - 1 {
+            size - 1
+        } else {
+            size - 2
+        };
+        if x % 2 == 0 {
+            path.extend((0..=bottom).rev().map(|y| Pos { x, y }));
+        } else {
+            path.extend((0..=bottom).map(|y| Pos { x, y }));
+        }
+    }
+    path.extend((1..size - 1).rev().map(|x| Pos { x, y: size - 1 }));
+    draw_loop(size as usize, size as usize, &path)
+}
+
+// Walks the loop on the old per-tile `HashMap<Pos, Vec<Pos>>` representation,
+// as a baseline for `bench`.
+fn walk_hashmap(d: &Diagram) -> usize {
+    let map: HashMap<Pos, Vec<Pos>> = (0..d.height)
+        .flat_map(|y| (0..d.width).map(move |x| Pos { x, y }))
+        .map(|pos| (pos, d.neighbours(pos).map(|(_, p)| p).collect()))
+        .collect();
+
+    let start = Instant::now();
+    let (mut cur_pos, mut last_pos, mut steps) = (d.start_pos, d.start_pos, 0);
+    loop {
+        let next = *map[&cur_pos]
+            .iter()
+            .find(|&&p| p != last_pos)
+            .expect("Loop should continue");
+        last_pos = cur_pos;
+        cur_pos = next;
+        steps += 1;
+        if cur_pos == d.start_pos {
+            println!("hashmap walk: {:?}", start.elapsed());
+            return steps;
+        }
+    }
+}
+
+struct GeneratedMaze {
+    map: String,
+    loop_length: usize,
+    area: usize,
+}
+
+fn set_connection(
+    flags: &mut [Vec<u8>],
+    (x1, y1): (usize, usize),
+    (x2, y2): (usize, usize),
+    on: bool,
+) {
+    let (dir, back) = if x1 == x2 {
+        (SOUTH, NORTH)
+    } else {
+        (EAST, WEST)
+    };
+    if on {
+        flags[y1][x1] |= dir;
+        flags[y2][x2] |= back;
+    } else {
+        flags[y1][x1] &= !dir;
+        flags[y2][x2] &= !back;
+    }
+}
+
+// Grows a random tree over a grid of 3x3 blocks and traces the wall around
+// it. Every block is a ring of pipe around one tile, and joining two blocks
+// opens the walls between them, so the loop is always a single closed path
+// and everything inside the blocks that isn't pipe is enclosed. Tiles off the
+// loop get a random junk pipe with probability `junk_density`.
+fn generate_maze(rng: &mut Rng, width: usize, height: usize, junk_density: f64) -> GeneratedMaze {
+    let (blocks_x, blocks_y) = ((width / 3).max(1), (height / 3).max(1));
+    let (width, height) = (width.max(3), height.max(3));
+    let mut flags = vec![vec![0u8; width]; height];
+
+    let target = 1 + rng.below(blocks_x * blocks_y);
+    let mut in_tree = vec![vec![false; blocks_x]; blocks_y];
+    let first = (rng.below(blocks_x), rng.below(blocks_y));
+    in_tree[first.1][first.0] = true;
+    let mut blocks = vec![first];
+    let mut joins = vec![];
+    while blocks.len() < target {
+        let (bx, by) = blocks[rng.below(blocks.len())];
+        let (dx, dy) = [(0, -1), (0, 1), (-1, 0), (1, 0)][rng.below(4)];
+        let (nx, ny) = (bx as i32 + dx, by as i32 + dy);
+        if nx < 0 || ny < 0 || nx >= blocks_x as i32 || ny >= blocks_y as i32 {
+            continue;
+        }
+        let next = (nx as usize, ny as usize);
+        if in_tree[next.1][next.0] {
+            continue;
+        }
+        in_tree[next.1][next.0] = true;
+        blocks.push(next);
+        joins.push(((bx, by).min(next), (bx, by).max(next)));
+    }
+
+    for &(bx, by) in &blocks {
+        let (x, y) = (3 * bx, 3 * by);
+        for i in 0..2 {
+            set_connection(&mut flags, (x + i, y), (x + i + 1, y), true);
+            set_connection(&mut flags, (x + i, y + 2), (x + i + 1, y + 2), true);
+            set_connection(&mut flags, (x, y + i), (x, y + i + 1), true);
+            set_connection(&mut flags, (x + 2, y + i), (x + 2, y + i + 1), true);
+        }
+    }
+    for &((ax, ay), (_, by)) in &joins {
+        let (x, y) = (3 * ax, 3 * ay);
+        if ay == by {
+            for i in 0..2 {
+                set_connection(&mut flags, (x + 2, y + i), (x + 2, y + i + 1), false);
+                set_connection(&mut flags, (x + 3, y + i), (x + 3, y + i + 1), false);
+            }
+            set_connection(&mut flags, (x + 2, y), (x + 3, y), true);
+            set_connection(&mut flags, (x + 2, y + 2), (x + 3, y + 2), true);
+        } else {
+            for i in 0..2 {
+                set_connection(&mut flags, (x + i, y + 2), (x + i + 1, y + 2), false);
+                set_connection(&mut flags, (x + i, y + 3), (x + i + 1, y + 3), false);
+            }
+            set_connection(&mut flags, (x, y + 2), (x, y + 3), true);
+            set_connection(&mut flags, (x + 2, y + 2), (x + 2, y + 3), true);
+        }
+    }
+
+    let loop_tiles: Vec<(usize, usize)> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| flags[y][x] != 0)
+        .collect();
+    let start = loop_tiles[rng.below(loop_tiles.len())];
+
+    let mut map = vec![vec!['.'; width]; height];
+    for (y, row) in map.iter_mut().enumerate() {
+        for (x, c) in row.iter_mut().enumerate() {
+            if (x, y) == start {
+                *c = 'S';
+            } else if flags[y][x] != 0 {
+                *c = PIPES
+                    .into_iter()
+                    .find(|&p| pipe_flags(p) == Some(flags[y][x]))
+                    .expect("Loop tiles should have two connections");
+            } else if rng.chance(junk_density) {
+                // Junk never points at the start, so its shape stays unique.
+                *c = PIPES[rng.below(PIPES.len())];
+                let points_at_start = DIRECTIONS.iter().any(|&(dir, dx, dy)| {
+                    pipe_flags(*c).unwrap() & dir != 0
+                        && (x as i32 + dx, y as i32 + dy) == (start.0 as i32, start.1 as i32)
+                });
+                if points_at_start {
+                    *c = '.';
                 }
-                _ => (),
-            }
-            max_x = x as i32;
-            max_y = y as i32;
-            diagram_map.insert(
-                Pos {
-                    x: x as i32,
-                    y: y as i32,
-                },
-                targets,
+            }
+        }
+    }
+
+    GeneratedMaze {
+        map: map
+            .iter()
+            .map(|row| row.iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n"),
+        loop_length: loop_tiles.len(),
+        area: 9 * blocks.len() - loop_tiles.len(),
+    }
+}
+
+// Times parsing, the loop walk, the distance BFS and the flood fill on a
+// 2000x2000 comb maze, checking the walk against the hashmap version and the
+// fill against the shoelace area.
+fn bench() {
+    const SIZE: i32 = 2000;
+    let input = comb_maze(SIZE);
+
+    let start = Instant::now();
+    let mut diagram = parse_diagram(&input).expect("Generated map should parse");
+    println!("parse: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let (steps, path) = try_paths(&mut diagram).expect("Generated map should have a loop");
+    println!("walk: {:?}", start.elapsed());
+    assert_eq!(walk_hashmap(&diagram), steps as usize);
+
+    let start = Instant::now();
+    get_loop_distances(&diagram);
+    println!("distances: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let area = get_enclosed(&diagram, &path).iter().filter(|e| **e).count();
+    println!("fill: {:?}", start.elapsed());
+    assert_eq!(area as i32, get_area_shoelace(&path));
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|a| a.as_str()) {
+        Some("--bench") => {
+            bench();
+            return;
+        }
+        Some("--generate") => {
+            let side = |i: usize| args.get(i).and_then(|a| a.parse::<usize>().ok());
+            let density = match args.get(4).map(|a| a.parse::<f64>()) {
+                None => Some(0.5),
+                Some(Ok(d)) if (0.0..=1.0).contains(&d) => Some(d),
+                Some(_) => None,
+            };
+            let seed = match args.get(5).map(|a| a.parse::<u64>()) {
+                None => Some(
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map_or(1, |t| t.as_nanos() as u64),
+                ),
+                Some(seed) => seed.ok(),
+            };
+            let (Some(width), Some(height), Some(density), Some(seed)) =
+                (side(2), side(3), density, seed)
+            else {
+                println!("Usage: day-10 --generate WIDTH HEIGHT [JUNK_DENSITY] [SEED]");
+                return;
+            };
+            let mut rng = Rng(seed.max(1));
+            let maze = generate_maze(&mut rng, width, height, density);
+            println!("{}", maze.map);
+            eprintln!(
+                "Loop length: {}, enclosed area: {}",
+                maze.loop_length, maze.area
             );
+            return;
         }
+        _ => (),
     }
 
-    let mut diagram: Diagram = Diagram {
-        map: diagram_map,
-        start_pos,
-        max_x,
-        max_y,
-    };
+    let mut diagram =
+        match parse_diagram(&fs::read_to_string("input.txt").expect("File should exist")) {
+            Ok(d) => d,
+            Err(e) => {
+                println!("Couldn't read the map: {}", e);
+                return;
+            }
+        };
     let result = try_paths(&mut diagram);
 
     match result {
         Ok(v) => {
-            println!("Part 1: {:?}", v.0 / 2);
-            println!("Part 2: {}", get_area(&v.1, diagram.max_x, diagram.max_y));
+            if let Some(c) = diagram.start_pipe {
+                println!("Start tile: {}", c);
+            }
+            let distances = get_loop_distances(&diagram);
+            let max_dist = distances
+                .iter()
+                .flatten()
+                .max()
+                .copied()
+                .expect("Start should have a distance");
+            let farthest: Vec<String> = distances
+                .iter()
+                .enumerate()
+                .filter(|(_, dist)| **dist == Some(max_dist))
+                .map(|(i, _)| {
+                    Pos {
+                        x: i as i32 % diagram.width,
+                        y: i as i32 / diagram.width,
+                    }
+                    .to_string()
+                })
+                .collect();
+            println!("Part 1: {:?}", max_dist);
+            println!("Farthest tile(s): {}", farthest.join("; "));
+            if env::args().any(|a| a == "--distances") {
+                println!("{}", render_distances(&diagram, &distances));
+            }
+            let area = get_area_shoelace(&v.1);
+            println!("Part 2: {}", area);
+
+            // The doubled-resolution flood fill is only kept as a cross-check.
+            if env::args().any(|a| a == "--cross-check") {
+                let fill = get_enclosed(&diagram, &v.1).iter().filter(|e| **e).count() as i32;
+                let scanline = get_area_scanline(&diagram, &v.1);
+                if fill == area && scanline == area {
+                    println!("Flood fill and scanline agree");
+                } else {
+                    println!(
+                        "Areas disagree: shoelace {}, scanline {}, flood fill {}",
+                        area, scanline, fill
+                    );
+                }
+            }
+            if env::args().any(|a| a == "--render") {
+                let enclosed = get_enclosed_scanline(&diagram, &v.1);
+                println!("{}", render(&diagram, &v.1, &enclosed));
+            }
         }
-        Err(_) => println!("Didn't find a path"),
+        Err(e) => println!("Didn't find a path: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs the loop search and every area method on random mazes and checks
+    // them against the generator's known loop length and enclosed area.
+    fn check_generated(runs: usize) {
+        let mut rng = Rng(0x2023_1210);
+        for run in 0..runs {
+            let (width, height) = (3 + rng.below(60), 3 + rng.below(60));
+            let density = rng.below(101) as f64 / 100.0;
+            let maze = generate_maze(&mut rng, width, height, density);
+
+            let mut diagram = parse_diagram(&maze.map).expect("Generated map should parse");
+            let (steps, path) = try_paths(&mut diagram)
+                .unwrap_or_else(|e| panic!("Run {}: no loop found: {}\n{}", run, e, maze.map));
+            let enclosed = get_enclosed(&diagram, &path).iter().filter(|e| **e).count();
+            let max_dist = get_loop_distances(&diagram).into_iter().flatten().max();
+
+            assert_eq!(
+                steps as usize, maze.loop_length,
+                "Run {}: loop length\n{}",
+                run, maze.map
+            );
+            assert_eq!(
+                max_dist,
+                Some(steps / 2),
+                "Run {}: farthest tile\n{}",
+                run,
+                maze.map
+            );
+            assert_eq!(
+                enclosed, maze.area,
+                "Run {}: flood fill area\n{}",
+                run, maze.map
+            );
+            assert_eq!(
+                get_area_shoelace(&path) as usize,
+                maze.area,
+                "Run {}: shoelace area",
+                run
+            );
+            assert_eq!(
+                get_area_scanline(&diagram, &path) as usize,
+                maze.area,
+                "Run {}: scanline area",
+                run
+            );
+        }
+    }
+
+    #[test]
+    fn generated_mazes() {
+        check_generated(200);
+    }
+
+    #[test]
+    #[ignore]
+    fn generated_mazes_many() {
+        check_generated(10_000);
     }
 }
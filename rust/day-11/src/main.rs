@@ -1,5 +1,11 @@
 use itertools::Itertools;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::env;
+use std::fmt;
 use std::fs;
+use std::iter::Sum;
+use std::str::FromStr;
 use std::vec;
 
 struct Galaxy {
@@ -7,12 +13,98 @@ struct Galaxy {
     y: usize,
 }
 
+#[derive(Clone, Copy)]
+enum Metric {
+    Manhattan,
+    Chebyshev,
+    Euclidean,
+}
+
+// Grid metrics stay exact integers, only Euclidean distances are rounded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Distance {
+    Exact(u128),
+    Approx(f64),
+}
+
+impl Distance {
+    fn as_f64(&self) -> f64 {
+        match *self {
+            Distance::Exact(d) => d as f64,
+            Distance::Approx(d) => d,
+        }
+    }
+
+    fn total_cmp(&self, other: &Distance) -> Ordering {
+        match (self, other) {
+            (Distance::Exact(a), Distance::Exact(b)) => a.cmp(b),
+            _ => self.as_f64().total_cmp(&other.as_f64()),
+        }
+    }
+}
+
+impl Sum for Distance {
+    fn sum<I: Iterator<Item = Distance>>(iter: I) -> Distance {
+        iter.fold(Distance::Exact(0), |total, d| match (total, d) {
+            (Distance::Exact(a), Distance::Exact(b)) => Distance::Exact(a + b),
+            _ => Distance::Approx(total.as_f64() + d.as_f64()),
+        })
+    }
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Distance::Exact(d) => write!(f, "{}", d),
+            Distance::Approx(d) => write!(f, "{}", d),
+        }
+    }
+}
+
+impl Metric {
+    fn distance(&self, a: &Galaxy, b: &Galaxy) -> Distance {
+        let x_diff = a.x.abs_diff(b.x);
+        let y_diff = a.y.abs_diff(b.y);
+        match self {
+            Metric::Manhattan => Distance::Exact((x_diff + y_diff) as u128),
+            Metric::Chebyshev => Distance::Exact(x_diff.max(y_diff) as u128),
+            Metric::Euclidean => Distance::Approx((x_diff as f64).hypot(y_diff as f64)),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manhattan" => Ok(Metric::Manhattan),
+            "chebyshev" => Ok(Metric::Chebyshev),
+            "euclidean" => Ok(Metric::Euclidean),
+            _ => Err(format!("Unknown metric '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Metric::Manhattan => "manhattan",
+            Metric::Chebyshev => "chebyshev",
+            Metric::Euclidean => "euclidean",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const MAX_RENDER_SIDE: usize = 400;
+
 struct Space {
     map: Vec<Vec<char>>,
     galaxies: Vec<Galaxy>,
     galaxies_expanded: Vec<Galaxy>,
-    row_expansions: Vec<usize>,
-    col_expansions: Vec<usize>,
+    empty_rows_before: Vec<usize>,
+    empty_cols_before: Vec<usize>,
 }
 
 impl Space {
@@ -28,73 +120,478 @@ impl Space {
         self.galaxies = galaxies;
     }
 
+    // Prefix counts of empty rows/columns: `empty_rows_before[y]` is the
+    // number of empty rows above row y, with one extra entry for the total.
     fn find_expansions(&mut self) {
-        for (y, row) in self.map.iter().enumerate() {
-            if !row.contains(&'#') {
-                self.row_expansions.push(y);
+        let width = self.map.first().expect("Map row should not be empty").len();
+        let mut col_has_galaxy = vec![false; width];
+
+        self.empty_rows_before = vec![0];
+        for row in &self.map {
+            let empty = !row.contains(&'#');
+            self.empty_rows_before
+                .push(self.empty_rows_before.last().unwrap() + empty as usize);
+            for (x, c) in row.iter().enumerate() {
+                col_has_galaxy[x] |= *c == '#';
             }
         }
 
-        for x in 0..self.map.first().expect("Map row should not be empty").len() {
-            if self.map.iter().all(|r| r.iter().nth(x).unwrap() != &'#') {
-                self.col_expansions.push(x);
-            }
+        self.empty_cols_before = vec![0];
+        for has_galaxy in col_has_galaxy {
+            self.empty_cols_before
+                .push(self.empty_cols_before.last().unwrap() + !has_galaxy as usize);
         }
     }
 
     fn expand(&mut self, n: usize) {
-        let mut galaxies: Vec<Galaxy> = vec![];
-        for galaxy in &self.galaxies {
-            let add_rows = self
-                .row_expansions
-                .iter()
-                .filter(|&y| y < &galaxy.y)
-                .count()
-                * (n - 1);
-            let add_cols = self
-                .col_expansions
-                .iter()
-                .filter(|&x| x < &galaxy.x)
-                .count()
-                * (n - 1);
-            galaxies.push(Galaxy {
-                x: galaxy.x + add_cols,
-                y: galaxy.y + add_rows,
+        self.galaxies_expanded = self
+            .galaxies
+            .iter()
+            .map(|galaxy| Galaxy {
+                x: galaxy.x + self.empty_cols_before[galaxy.x] * (n - 1),
+                y: galaxy.y + self.empty_rows_before[galaxy.y] * (n - 1),
             })
+            .collect();
+    }
+
+    // Draws the universe expanded by `n` like the puzzle example, with galaxies
+    // numbered when there are few enough to fit a digit in each cell.
+    fn render(&self, n: usize) -> Option<String> {
+        let width = self.empty_cols_before.len() - 1;
+        let height = self.empty_rows_before.len() - 1;
+        let expanded_width = width + self.empty_cols_before[width] * (n - 1);
+        let expanded_height = height + self.empty_rows_before[height] * (n - 1);
+        if expanded_width > MAX_RENDER_SIDE || expanded_height > MAX_RENDER_SIDE {
+            return None;
         }
-        self.galaxies_expanded = galaxies
+
+        let mut picture = vec![vec!['.'; expanded_width]; expanded_height];
+        let numbered = self.galaxies_expanded.len() < 10;
+        for (i, galaxy) in self.galaxies_expanded.iter().enumerate() {
+            picture[galaxy.y][galaxy.x] = if numbered {
+                char::from_digit(i as u32 + 1, 10).unwrap()
+            } else {
+                '#'
+            };
+        }
+        Some(
+            picture
+                .iter()
+                .map(|row| row.iter().collect::<String>())
+                .collect::<Vec<String>>()
+                .join("\n"),
+        )
+    }
+
+    // Manhattan and Chebyshev sums separate into independent 1D sums, which
+    // are computed from sorted coordinates in O(n log n). Chebyshev uses the
+    // rotation u = x + y, v = x - y, where max(|dx|, |dy|) = (|du| + |dv|) / 2.
+    fn get_distances(&self, metric: Metric) -> Distance {
+        let coords =
+            |f: fn(&Galaxy) -> i64| -> Vec<i64> { self.galaxies_expanded.iter().map(f).collect() };
+        match metric {
+            Metric::Manhattan => Distance::Exact(
+                sum_pairwise_diffs(coords(|g| g.x as i64))
+                    + sum_pairwise_diffs(coords(|g| g.y as i64)),
+            ),
+            Metric::Chebyshev => Distance::Exact(
+                (sum_pairwise_diffs(coords(|g| (g.x + g.y) as i64))
+                    + sum_pairwise_diffs(coords(|g| g.x as i64 - g.y as i64)))
+                    / 2,
+            ),
+            Metric::Euclidean => self.get_distances_pairwise(metric),
+        }
+    }
+
+    fn get_distances_pairwise(&self, metric: Metric) -> Distance {
+        self.galaxies_expanded
+            .iter()
+            .tuple_combinations()
+            .map(|(a, b)| metric.distance(a, b))
+            .sum()
     }
 
-    fn get_distances(&self) -> usize {
-        let mut length_sum = 0;
-        for pair in self.galaxies_expanded.iter().combinations(2) {
-            let x_diff = pair[0].x.max(pair[1].x) - pair[0].x.min(pair[1].x);
-            let y_diff = pair[0].y.max(pair[1].y) - pair[0].y.min(pair[1].y);
-            length_sum += x_diff + y_diff;
+    // Distance between every pair of expanded galaxies, indexed from 0.
+    fn get_pair_distances(&self, metric: Metric) -> Vec<(usize, usize, Distance)> {
+        self.galaxies_expanded
+            .iter()
+            .enumerate()
+            .tuple_combinations()
+            .map(|((i, a), (j, b))| (i, j, metric.distance(a, b)))
+            .collect()
+    }
+
+    // Shortest walking distance between every pair of galaxies when tiles
+    // holding one of `obstacles` can't be entered. Stepping into an empty row
+    // or column costs `n`, like in `expand`. Unreachable pairs are `None`.
+    fn get_path_distances(
+        &self,
+        n: usize,
+        obstacles: &[char],
+    ) -> Vec<(usize, usize, Option<usize>)> {
+        let mut pairs = vec![];
+        for (i, start) in self.galaxies.iter().enumerate() {
+            let dist = self.shortest_paths_from(start, n, obstacles);
+            for (j, end) in self.galaxies.iter().enumerate().skip(i + 1) {
+                pairs.push((i, j, dist[end.y][end.x]));
+            }
+        }
+        pairs
+    }
+
+    fn shortest_paths_from(
+        &self,
+        start: &Galaxy,
+        n: usize,
+        obstacles: &[char],
+    ) -> Vec<Vec<Option<usize>>> {
+        let height = self.empty_rows_before.len() - 1;
+        let width = self.empty_cols_before.len() - 1;
+        let row_cost =
+            |y: usize| 1 + (self.empty_rows_before[y + 1] - self.empty_rows_before[y]) * (n - 1);
+        let col_cost =
+            |x: usize| 1 + (self.empty_cols_before[x + 1] - self.empty_cols_before[x]) * (n - 1);
+
+        let mut dist = vec![vec![None; width]; height];
+        let mut queue = BinaryHeap::new();
+        dist[start.y][start.x] = Some(0);
+        queue.push(Reverse((0, start.x, start.y)));
+
+        while let Some(Reverse((d, x, y))) = queue.pop() {
+            if dist[y][x].is_some_and(|best| best < d) {
+                continue;
+            }
+            let mut neighbours = vec![];
+            if x > 0 {
+                neighbours.push((x - 1, y, col_cost(x - 1)));
+            }
+            if x + 1 < width {
+                neighbours.push((x + 1, y, col_cost(x + 1)));
+            }
+            if y > 0 {
+                neighbours.push((x, y - 1, row_cost(y - 1)));
+            }
+            if y + 1 < height {
+                neighbours.push((x, y + 1, row_cost(y + 1)));
+            }
+
+            for (nx, ny, cost) in neighbours {
+                if obstacles.contains(&self.map[ny][nx]) {
+                    continue;
+                }
+                let next = d + cost;
+                if dist[ny][nx].is_none_or(|best| next < best) {
+                    dist[ny][nx] = Some(next);
+                    queue.push(Reverse((next, nx, ny)));
+                }
+            }
         }
-        length_sum
+        dist
     }
 }
 
-fn main() {
-    let map: Vec<Vec<char>> = fs::read_to_string("input.txt")
-        .expect("File should open")
-        .split("\n")
+// Sum of |a - b| over all pairs of values, using a running prefix sum.
+fn sum_pairwise_diffs(mut values: Vec<i64>) -> u128 {
+    values.sort_unstable();
+    let (mut total, mut prefix) = (0, 0);
+    for (i, &v) in values.iter().enumerate() {
+        total += (v as i128 * i as i128 - prefix) as u128;
+        prefix += v as i128;
+    }
+    total
+}
+
+fn parse_space(input: &str) -> Space {
+    let map: Vec<Vec<char>> = input
+        .lines()
+        .filter(|l| !l.is_empty())
         .map(|r| r.chars().collect())
         .collect();
+    let width = map.first().expect("Map should have a row").len();
+    for (y, row) in map.iter().enumerate() {
+        assert!(
+            row.len() == width,
+            "Row {} should have {} cells, found {}",
+            y + 1,
+            width,
+            row.len()
+        );
+    }
+
     let mut space = Space {
         map,
         galaxies: vec![],
         galaxies_expanded: vec![],
-        row_expansions: vec![],
-        col_expansions: vec![],
+        empty_rows_before: vec![],
+        empty_cols_before: vec![],
     };
     space.add_galaxies();
     space.find_expansions();
+    space
+}
+
+struct Options {
+    factors: Vec<usize>,
+    metric: Metric,
+    list_pairs: bool,
+    extremes: bool,
+    render: bool,
+    list_galaxies: bool,
+    obstacles: Vec<char>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        factors: vec![],
+        metric: Metric::Manhattan,
+        list_pairs: false,
+        extremes: false,
+        render: false,
+        list_galaxies: false,
+        obstacles: vec![],
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--factor" => {
+                let factor = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&n| n >= 1)
+                    .ok_or("--factor needs a number of at least 1")?;
+                options.factors.push(factor);
+            }
+            "--metric" => {
+                options.metric = args.next().ok_or("--metric needs a name")?.parse()?;
+            }
+            "--pairs" => options.list_pairs = true,
+            "--extremes" => options.extremes = true,
+            "--render" => options.render = true,
+            "--galaxies" => options.list_galaxies = true,
+            "--obstacles" => {
+                options.obstacles = args
+                    .next()
+                    .ok_or("--obstacles needs the impassable characters")?
+                    .chars()
+                    .collect();
+            }
+            _ => return Err(format!("Unknown argument '{}'", arg)),
+        }
+    }
+    Ok(options)
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!(
+                "Usage: day-11 [--factor N]... [--metric manhattan|chebyshev|euclidean] [--pairs] [--extremes] [--render] [--galaxies] [--obstacles CHARS]"
+            );
+            return;
+        }
+    };
+    let mut space = parse_space(&fs::read_to_string("input.txt").expect("File should open"));
+
+    // Without --factor the puzzle's two expansions are used, and labelled as
+    // the two parts.
+    let default_factors = options.factors.is_empty();
+    let factors = if default_factors {
+        vec![2, 1_000_000]
+    } else {
+        options.factors.clone()
+    };
+
+    for (part, &factor) in factors.iter().enumerate() {
+        space.expand(factor);
+        if default_factors {
+            println!("Part {}: {}", part + 1, space.get_distances(options.metric));
+        } else {
+            println!(
+                "Factor {} ({}): {}",
+                factor,
+                options.metric,
+                space.get_distances(options.metric)
+            );
+        }
+
+        if options.render {
+            match space.render(factor) {
+                Some(picture) => println!("{}", picture),
+                None => println!("  Expanded universe is too large to render"),
+            }
+        }
+        if options.list_galaxies {
+            for (i, (before, after)) in space
+                .galaxies
+                .iter()
+                .zip(&space.galaxies_expanded)
+                .enumerate()
+            {
+                println!(
+                    "  Galaxy {}: ({}, {}) -> ({}, {})",
+                    i + 1,
+                    before.x,
+                    before.y,
+                    after.x,
+                    after.y
+                );
+            }
+        }
+
+        if !options.obstacles.is_empty() {
+            let paths = space.get_path_distances(factor, &options.obstacles);
+            let unreachable = paths.iter().filter(|(_, _, d)| d.is_none()).count();
+            println!(
+                "  Around obstacles: {} ({} unreachable pairs)",
+                paths.iter().filter_map(|(_, _, d)| *d).sum::<usize>(),
+                unreachable
+            );
+        }
+
+        if !options.list_pairs && !options.extremes {
+            continue;
+        }
+        let pairs = space.get_pair_distances(options.metric);
+        if options.list_pairs {
+            for (i, j, d) in &pairs {
+                println!("  {} - {}: {}", i + 1, j + 1, d);
+            }
+        }
+        if options.extremes {
+            let by_distance =
+                |a: &&(usize, usize, Distance), b: &&(usize, usize, Distance)| a.2.total_cmp(&b.2);
+            if let (Some(min), Some(max)) = (
+                pairs.iter().min_by(by_distance),
+                pairs.iter().max_by(by_distance),
+            ) {
+                println!("  Closest: {} - {}: {}", min.0 + 1, min.1 + 1, min.2);
+                println!("  Farthest: {} - {}: {}", max.0 + 1, max.1 + 1, max.2);
+            }
+        }
+    }
+}
 
-    space.expand(2);
-    println!("Part 1: {}", space.get_distances());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xorshift::Rng;
 
-    space.expand(1_000_000);
-    println!("Part 2: {}", space.get_distances());
+    fn random_space(rng: &mut Rng, side: usize, galaxies: usize) -> Space {
+        let mut map = vec![vec!['.'; side]; side];
+        for _ in 0..galaxies {
+            map[rng.below(side)][rng.below(side)] = '#';
+        }
+        let mut space = Space {
+            map,
+            galaxies: vec![],
+            galaxies_expanded: vec![],
+            empty_rows_before: vec![],
+            empty_cols_before: vec![],
+        };
+        space.add_galaxies();
+        space.find_expansions();
+        space
+    }
+
+    // Compares the sorted-coordinate sums against the pairwise ones.
+    fn check_sums(seed: u64, side: usize, sizes: &[usize]) {
+        let mut rng = Rng(seed);
+        for &galaxies in sizes {
+            let mut space = random_space(&mut rng, side, galaxies);
+            space.expand(2 + rng.below(10));
+            for metric in [Metric::Manhattan, Metric::Chebyshev] {
+                assert_eq!(
+                    space.get_distances(metric),
+                    space.get_distances_pairwise(metric),
+                    "Sums should match for {}",
+                    metric
+                );
+            }
+        }
+    }
+
+    const EXAMPLE: &str = "...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....
+";
+
+    #[test]
+    fn example_distances() {
+        let mut space = parse_space(EXAMPLE);
+        for (factor, total) in [(2, 374), (10, 1030), (100, 8410)] {
+            space.expand(factor);
+            assert_eq!(
+                space.get_distances(Metric::Manhattan),
+                Distance::Exact(total)
+            );
+            let paths = space.get_path_distances(factor, &[]);
+            assert!(paths.iter().all(|(_, _, d)| d.is_some()));
+            let sum: usize = paths.iter().filter_map(|(_, _, d)| *d).sum();
+            assert_eq!(sum as u128, total, "Open paths for factor {}", factor);
+        }
+    }
+
+    #[test]
+    fn example_obstacles() {
+        // A wall across the empty fourth row, first with a gap at the right
+        // edge and then closed off.
+        let with_gap = EXAMPLE.replacen("\n..........\n", "\nxxxxxxxxx.\n", 1);
+        let space = parse_space(&with_gap);
+        let paths = space.get_path_distances(2, &['x']);
+        let sum: usize = paths.iter().filter_map(|(_, _, d)| *d).sum();
+        assert_eq!(sum, 538);
+        assert!(paths.iter().all(|(_, _, d)| d.is_some()));
+
+        let closed = EXAMPLE.replacen("\n..........\n", "\nxxxxxxxxxx\n", 1);
+        let space = parse_space(&closed);
+        let paths = space.get_path_distances(2, &['x']);
+        let sum: usize = paths.iter().filter_map(|(_, _, d)| *d).sum();
+        assert_eq!(sum, 157);
+        assert_eq!(paths.iter().filter(|(_, _, d)| d.is_none()).count(), 18);
+    }
+
+    #[test]
+    fn example_render() {
+        let mut space = parse_space(EXAMPLE);
+        space.expand(2);
+        let picture = space
+            .render(2)
+            .expect("Example should be small enough to render")
+            .replace(|c: char| c.is_ascii_digit(), "#");
+        assert_eq!(
+            picture,
+            "....#........
+.........#...
+#............
+.............
+.............
+........#....
+.#...........
+............#
+.............
+.............
+.........#...
+#....#......."
+        );
+    }
+
+    #[test]
+    fn sorted_sums_match_pairwise() {
+        check_sums(0x2023_1211, 200, &[1, 2, 10, 100, 1_000]);
+    }
+
+    // The pairwise side is quadratic, run with --release.
+    #[test]
+    #[ignore]
+    fn sorted_sums_match_pairwise_large() {
+        check_sums(0x2023_1211, 2_000, &[1_000, 10_000, 30_000]);
+    }
 }
@@ -1,27 +1,97 @@
-use std::fs;
+use std::{env, fmt, fs, time::Instant};
+use xorshift::Rng;
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum Line {
     Vertical(usize),
     Horizontal(usize),
 }
 
+impl Line {
+    fn summary(&self) -> usize {
+        match self {
+            Line::Vertical(x) => *x,
+            Line::Horizontal(y) => 100 * y,
+        }
+    }
+
+    fn mirror(&self, (y, x): (usize, usize)) -> (usize, usize) {
+        match self {
+            Line::Vertical(x_line) => (y, 2 * x_line - x - 1),
+            Line::Horizontal(y_line) => (2 * y_line - y - 1, x),
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Vertical(x) => write!(f, "vertical between cols {} and {}", x, x + 1),
+            Line::Horizontal(y) => write!(f, "horizontal between rows {} and {}", y, y + 1),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Smudge {
+    y: usize,
+    x: usize,
+    line: Line,
+}
+
+#[derive(Debug)]
+struct Reflection {
+    line: Line,
+    differences: Vec<((usize, usize), (usize, usize))>,
+}
+
+// Rows and columns are stored as bitmasks with '#' as a set bit, so a pattern
+// can be at most 128 cells wide and high.
+const MAX_SIDE: usize = u128::BITS as usize;
+
 struct Pattern {
     map: Vec<Vec<char>>,
+    rows: Vec<u128>,
+    cols: Vec<u128>,
     width: usize,
     height: usize,
-    part_1_line: Option<Line>,
 }
 
 impl Pattern {
     fn new(map: Vec<Vec<char>>) -> Pattern {
         let height = map.len();
         let width = map.first().expect("Should have at least 1 row").len();
+        assert!(
+            width <= MAX_SIDE && height <= MAX_SIDE,
+            "Pattern should be at most {MAX_SIDE}x{MAX_SIDE}"
+        );
+        for (y, row) in map.iter().enumerate() {
+            assert!(
+                row.len() == width,
+                "Row {} should have {} cells, found {}",
+                y + 1,
+                width,
+                row.len()
+            );
+        }
+
+        let mut rows = vec![0; height];
+        let mut cols = vec![0; width];
+        for (y, row) in map.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                if *c == '#' {
+                    rows[y] |= 1 << x;
+                    cols[x] |= 1 << y;
+                }
+            }
+        }
+
         Pattern {
             map,
+            rows,
+            cols,
             width,
-            height: height,
-            part_1_line: None,
+            height,
         }
     }
 
@@ -31,103 +101,391 @@ impl Pattern {
         } else {
             self.map[y][x] = '.'
         }
+        self.rows[y] ^= 1 << x;
+        self.cols[x] ^= 1 << y;
+    }
+
+    fn candidate_lines(&self) -> impl Iterator<Item = Line> {
+        (1..self.width)
+            .map(Line::Vertical)
+            .chain((1..self.height).map(Line::Horizontal))
     }
 
-    fn check_smudges(&mut self) -> Option<usize> {
-        for y in 0..self.height {
-            for x in 0..self.width {
+    // Cells on the left/top side of `line` that differ from their mirror image.
+    // Stops as soon as more than `limit` differences have been found.
+    fn differing_cells(&self, line: Line, limit: usize) -> Vec<(usize, usize)> {
+        let mut cells = vec![];
+        let (masks, line_pos) = match line {
+            Line::Vertical(x_line) => (&self.cols, x_line),
+            Line::Horizontal(y_line) => (&self.rows, y_line),
+        };
+        let edge_dist = line_pos.min(masks.len() - line_pos);
 
-                self.reverse_point(y, x);
-                if let Some(v) = self.find_refl_across_vert() {
-                    return Some(v);
-                }
-                if let Some(v) = self.find_refl_across_hor() {
-                    return Some(v * 100);
+        for offset in 0..edge_dist {
+            let pos = line_pos - offset - 1;
+            let mut diff = masks[pos] ^ masks[line_pos + offset];
+            while diff != 0 {
+                let bit = diff.trailing_zeros() as usize;
+                diff &= diff - 1;
+                cells.push(match line {
+                    Line::Vertical(_) => (bit, pos),
+                    Line::Horizontal(_) => (pos, bit),
+                });
+                if cells.len() > limit {
+                    return cells;
                 }
-
-                self.reverse_point(y, x);
             }
         }
-        None
+        cells
     }
 
-    fn find_refl_across_hor(&mut self) -> Option<usize> {
-        'find_loop: for y_line in 1..self.height {
-            let edge_dist = y_line.min(self.height - y_line);
-
-            for x in 0..self.width {
-                for y_offset in 0..=edge_dist {
-                    if self.map[y_line - y_offset][x] != self.map[y_line + y_offset - 1][x] {
-                        continue 'find_loop;
+    // Char-grid version of `differing_cells`, kept as a baseline for `bench`.
+    fn differing_cells_grid(&self, line: Line, limit: usize) -> Vec<(usize, usize)> {
+        let mut cells = vec![];
+        match line {
+            Line::Vertical(x_line) => {
+                let edge_dist = x_line.min(self.width - x_line);
+                for y in 0..self.height {
+                    for x_offset in 0..edge_dist {
+                        let x = x_line - x_offset - 1;
+                        if self.map[y][x] != self.map[y][x_line + x_offset] {
+                            cells.push((y, x));
+                            if cells.len() > limit {
+                                return cells;
+                            }
+                        }
                     }
                 }
             }
-
-            if self.part_1_line == None {
-                self.part_1_line = Some(Line::Horizontal(y_line));
-                return Some(y_line);
-            }
-            if Some(Line::Horizontal(y_line)) != self.part_1_line {
-                return Some(y_line);
+            Line::Horizontal(y_line) => {
+                let edge_dist = y_line.min(self.height - y_line);
+                for y_offset in 0..edge_dist {
+                    let y = y_line - y_offset - 1;
+                    for x in 0..self.width {
+                        if self.map[y][x] != self.map[y_line + y_offset][x] {
+                            cells.push((y, x));
+                            if cells.len() > limit {
+                                return cells;
+                            }
+                        }
+                    }
+                }
             }
         }
-        None
+        cells
     }
 
-    fn find_refl_across_vert(&mut self) -> Option<usize> {
-        'find_loop: for x_line in 1..self.width {
-            let edge_dist = x_line.min(self.width - x_line);
+    // Every reflection line that would be exact if precisely `smudges` cells
+    // were flipped, together with the mismatched cell pairs across each line.
+    fn reflections_with_smudges(&self, smudges: usize) -> Vec<Reflection> {
+        self.candidate_lines()
+            .filter_map(|line| {
+                let cells = self.differing_cells(line, smudges);
+                (cells.len() == smudges).then(|| Reflection {
+                    line,
+                    differences: cells.into_iter().map(|c| (c, line.mirror(c))).collect(),
+                })
+            })
+            .collect()
+    }
 
-            for y in 0..self.height {
-                for x_offset in 0..=edge_dist {
-                    if self.map[y][x_line - x_offset] != self.map[y][x_line + x_offset - 1] {
-                        continue 'find_loop;
-                    }
+    fn find_smudge(&self) -> Option<Smudge> {
+        self.reflections_with_smudges(1)
+            .into_iter()
+            .next()
+            .map(|refl| {
+                let ((y, x), _) = refl.differences[0];
+                Smudge {
+                    y,
+                    x,
+                    line: refl.line,
                 }
-            }
+            })
+    }
 
-            if self.part_1_line == None {
-                self.part_1_line = Some(Line::Vertical(x_line));
-                return Some(x_line);
-            }
-            if Some(Line::Vertical(x_line)) != self.part_1_line {
-                return Some(x_line);
-            }
+    fn find_line(&self) -> Option<Line> {
+        self.find_refl_across_vert()
+            .map(Line::Vertical)
+            .or_else(|| self.find_refl_across_hor().map(Line::Horizontal))
+    }
+
+    // Draws the pattern with the mirror line marked as in the puzzle text
+    // (`><` over the columns, `v`/`^` beside the rows) and the smudge as '*'.
+    fn render(&self, line: Line, smudge: (usize, usize)) -> String {
+        let margin = " ".repeat(5);
+        let col_marks: String = (0..self.width)
+            .map(|x| match line {
+                Line::Vertical(x_line) if x + 1 == x_line => '>',
+                Line::Vertical(x_line) if x == x_line => '<',
+                _ => ' ',
+            })
+            .collect();
+
+        let mut lines = vec![
+            format!(
+                "{margin}{}",
+                (1..=self.width)
+                    .map(|x| char::from_digit((x % 10) as u32, 10).unwrap())
+                    .collect::<String>()
+            ),
+            format!("{margin}{col_marks}"),
+        ];
+        for (y, row) in self.map.iter().enumerate() {
+            let row_mark = match line {
+                Line::Horizontal(y_line) if y + 1 == y_line => 'v',
+                Line::Horizontal(y_line) if y == y_line => '^',
+                _ => ' ',
+            };
+            let cells: String = row
+                .iter()
+                .enumerate()
+                .map(|(x, c)| if (y, x) == smudge { '*' } else { *c })
+                .collect();
+            lines.push(format!("{:>3} {row_mark}{cells}{row_mark}", y + 1));
+        }
+        lines.push(format!("{margin}{col_marks}"));
+        lines.join("\n")
+    }
+
+    fn find_refl_across_hor(&self) -> Option<usize> {
+        Self::find_refl(&self.rows)
+    }
+
+    fn find_refl_across_vert(&self) -> Option<usize> {
+        Self::find_refl(&self.cols)
+    }
+
+    fn find_refl(masks: &[u128]) -> Option<usize> {
+        (1..masks.len()).find(|&line_pos| {
+            let edge_dist = line_pos.min(masks.len() - line_pos);
+            (0..edge_dist).all(|offset| masks[line_pos - offset - 1] == masks[line_pos + offset])
+        })
+    }
+}
+
+fn generate_pattern(rng: &mut Rng, width: usize, height: usize) -> Pattern {
+    let mut map: Vec<Vec<char>> = (0..height)
+        .map(|_| {
+            (0..width)
+                .map(|_| if rng.next_u64() & 1 == 0 { '#' } else { '.' })
+                .collect()
+        })
+        .collect();
+
+    let x_line = 1 + rng.below(width - 1);
+    for row in map.iter_mut() {
+        for offset in 0..x_line.min(width - x_line) {
+            row[x_line + offset] = row[x_line - offset - 1];
         }
-        None
     }
+
+    let mut pattern = Pattern::new(map);
+    pattern.reverse_point(rng.below(height), rng.below(width));
+    pattern
 }
 
-fn main() {
-    let (mut total_1, mut total_2) = (0, 0);
+type DifferingCellsFn = fn(&Pattern, Line, usize) -> Vec<(usize, usize)>;
 
-    for (_, section) in fs::read_to_string("input.txt")
-        .expect("File should exist")
-        .split("\n\n")
-        .enumerate()
-    {
-        let map: Vec<Vec<char>> = section
-            .split("\n")
-            .map(|s| s.chars().collect::<Vec<char>>())
+fn bench() {
+    const PATTERNS: usize = 1_000;
+    const SIDE: usize = MAX_SIDE;
+
+    let mut rng = Rng(0x2023_1213);
+    let patterns: Vec<Pattern> = (0..PATTERNS)
+        .map(|_| generate_pattern(&mut rng, SIDE, SIDE))
+        .collect();
+
+    let methods: [(&str, DifferingCellsFn); 2] = [
+        ("char grid", Pattern::differing_cells_grid),
+        ("bitmask", Pattern::differing_cells),
+    ];
+    let mut results = vec![];
+    for (name, differing_cells) in methods {
+        let start = Instant::now();
+        let smudges: Vec<Vec<Line>> = patterns
+            .iter()
+            .map(|p| {
+                p.candidate_lines()
+                    .filter(|line| differing_cells(p, *line, 1).len() == 1)
+                    .collect()
+            })
             .collect();
-        let mut pattern = Pattern::new(map);
+        println!(
+            "{name}: {PATTERNS} patterns of {SIDE}x{SIDE} in {:?}",
+            start.elapsed()
+        );
+        results.push(smudges);
+    }
+    assert!(
+        results.windows(2).all(|w| w[0] == w[1]),
+        "Methods should agree"
+    );
+}
 
-        match pattern.find_refl_across_vert() {
-            Some(v) => {
-                total_1 += v;
-            }
-            None => match pattern.find_refl_across_hor() {
-                Some(v) => {
-                    total_1 += 100 * v;
-                }
-                None => println!("No line found for part 1"),
-            },
+fn parse_patterns(input: &str) -> Vec<Pattern> {
+    input
+        .split("\n\n")
+        .filter(|section| !section.trim().is_empty())
+        .map(|section| {
+            let map: Vec<Vec<char>> = section
+                .lines()
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(|s| s.chars().collect::<Vec<char>>())
+                .collect();
+            Pattern::new(map)
+        })
+        .collect()
+}
+
+fn list_smudged_reflections(patterns: &[Pattern], smudges: usize) {
+    for (i, pattern) in patterns.iter().enumerate() {
+        for refl in pattern.reflections_with_smudges(smudges) {
+            let pairs: Vec<String> = refl
+                .differences
+                .iter()
+                .map(|((y1, x1), (y2, x2))| {
+                    format!("({},{})/({},{})", y1 + 1, x1 + 1, y2 + 1, x2 + 1)
+                })
+                .collect();
+            println!("Pattern {}: {} [{}]", i + 1, refl.line, pairs.join(" "));
+        }
+    }
+}
+
+fn main() {
+    let mode = env::args().nth(1);
+    if mode.as_deref() == Some("bench") {
+        bench();
+        return;
+    }
+    let patterns = parse_patterns(&fs::read_to_string("input.txt").expect("File should exist"));
+    if mode.as_deref() == Some("smudges") {
+        let smudges = env::args()
+            .nth(2)
+            .and_then(|k| k.parse().ok())
+            .expect("Should give a smudge count");
+        list_smudged_reflections(&patterns, smudges);
+        return;
+    }
+    let report = mode.as_deref() == Some("report");
+
+    let (mut total_1, mut total_2) = (0, 0);
+
+    for (i, mut pattern) in patterns.into_iter().enumerate() {
+        let part_1_line = pattern.find_line();
+        match part_1_line {
+            Some(line) => total_1 += line.summary(),
+            None => println!("No line found for part 1"),
         }
 
-        if let Some(v) = pattern.check_smudges() {
-            total_2 += v;
+        match pattern.find_smudge() {
+            Some(smudge) => {
+                let old_cell = pattern.map[smudge.y][smudge.x];
+                pattern.reverse_point(smudge.y, smudge.x);
+                debug_assert!(pattern.differing_cells(smudge.line, 0).is_empty());
+                total_2 += smudge.line.summary();
+
+                if report {
+                    println!(
+                        "Pattern {}: smudge at row {}, col {} ('{}' -> '{}'), line {} -> {}",
+                        i + 1,
+                        smudge.y + 1,
+                        smudge.x + 1,
+                        old_cell,
+                        pattern.map[smudge.y][smudge.x],
+                        part_1_line.map_or("none".to_string(), |l| l.to_string()),
+                        smudge.line,
+                    );
+                    println!("{}", pattern.render(smudge.line, (smudge.y, smudge.x)));
+                }
+            }
+            None if report => println!("Pattern {}: no smudge found", i + 1),
+            None => {}
         }
     }
     println!("Part 1: {}", total_1);
     println!("Part 2: {}", total_2);
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.
+
+#...##..#
+#....#..#
+..##..###
+#####.##.
+#####.##.
+..##..###
+#....#..#
+";
+
+    #[test]
+    fn example_reflections() {
+        let patterns = parse_patterns(EXAMPLE);
+        let lines = |smudges: usize| -> Vec<Vec<Line>> {
+            patterns
+                .iter()
+                .map(|p| {
+                    p.reflections_with_smudges(smudges)
+                        .iter()
+                        .map(|r| r.line)
+                        .collect()
+                })
+                .collect()
+        };
+        assert_eq!(
+            lines(0),
+            [vec![Line::Vertical(5)], vec![Line::Horizontal(4)]]
+        );
+        assert_eq!(
+            lines(1),
+            [vec![Line::Horizontal(3)], vec![Line::Horizontal(1)]]
+        );
+    }
+
+    #[test]
+    fn example_smudges() {
+        let patterns = parse_patterns(EXAMPLE);
+        let differences: Vec<_> = patterns
+            .iter()
+            .map(|p| p.reflections_with_smudges(1)[0].differences.clone())
+            .collect();
+        // The puzzle's smudges, at row 1 col 1 and row 1 col 5, with the cells
+        // they are mirrored onto.
+        assert_eq!(
+            differences,
+            [vec![((0, 0), (5, 0))], vec![((0, 4), (1, 4))]]
+        );
+    }
+
+    #[test]
+    fn bitmask_matches_grid() {
+        let mut rng = Rng(0x2023_1213);
+        let random = (0..50).map(|_| {
+            let (width, height) = (2 + rng.below(39), 1 + rng.below(40));
+            generate_pattern(&mut rng, width, height)
+        });
+        for pattern in parse_patterns(EXAMPLE)
+            .into_iter()
+            .chain(random.collect::<Vec<_>>())
+        {
+            for line in pattern.candidate_lines() {
+                let mut bitmask = pattern.differing_cells(line, usize::MAX);
+                let mut grid = pattern.differing_cells_grid(line, usize::MAX);
+                bitmask.sort_unstable();
+                grid.sort_unstable();
+                assert_eq!(bitmask, grid, "Differing cells for {}", line);
+            }
+        }
+    }
+}
@@ -0,0 +1,724 @@
+use num::integer::{lcm, Integer};
+use regex::Regex;
+use std::{collections::HashMap, env, fmt, fs, process, time::Instant};
+
+// Accepts any node name that doesn't contain whitespace or the punctuation
+// used by the `NAME = (LEFT, RIGHT)` syntax.
+const NODE_LINE: &str = r"^\s*([^\s=(),]+)\s*=\s*\(\s*([^\s=(),]+)\s*,\s*([^\s=(),]+)\s*\)\s*$";
+
+#[derive(Debug)]
+enum NavError {
+    BadInstruction { col: usize, c: char },
+    NoInstructions,
+    NoNodes,
+    Undefined(Vec<String>),
+    NoStart(String),
+    Unreachable { start: String, goal: String },
+    NeverTogether { start: String, goal: String },
+}
+
+impl fmt::Display for NavError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NavError::BadInstruction { col, c } => {
+                write!(f, "instruction {:?} at column {} is not L or R", c, col)
+            }
+            NavError::NoInstructions => write!(f, "instruction line is empty"),
+            NavError::NoNodes => {
+                write!(f, "no blank line between the instructions and the nodes")
+            }
+            NavError::Undefined(names) => {
+                write!(f, "nodes used but never defined: {}", names.join(", "))
+            }
+            NavError::NoStart(pattern) => write!(f, "no node matches start pattern {}", pattern),
+            NavError::Unreachable { start, goal } => {
+                write!(f, "{} cycles without ever reaching {}", start, goal)
+            }
+            NavError::NeverTogether { start, goal } => {
+                write!(f, "{} never reaches {} on every path at once", start, goal)
+            }
+        }
+    }
+}
+
+// Node names interned to dense ids, with the left/right successor of each
+// node stored by id so a walk never touches a string.
+struct Network {
+    names: Vec<String>,
+    ids: HashMap<String, u32>,
+    next: Vec<[u32; 2]>,
+}
+
+impl Network {
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.next.push([id, id]);
+        id
+    }
+
+    fn id(&self, name: &str) -> u32 {
+        *self.ids.get(name).expect("Node should exist")
+    }
+
+    fn step(&self, node: u32, dir: usize) -> u32 {
+        self.next[node as usize][dir]
+    }
+
+    fn matching(&self, pattern: &str) -> Vec<u32> {
+        (0..self.names.len() as u32)
+            .filter(|&id| glob_match(pattern, &self.names[id as usize]))
+            .collect()
+    }
+}
+
+// `*` matches any run of characters and `?` a single one, so `AAA` is an exact
+// name and `*Z` is every node ending in Z.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = backtrack {
+            backtrack = Some((star_p, star_n + 1));
+            p = star_p + 1;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// L and R become indices into a node's successors.
+fn parse_instructions(line: &str) -> Result<Vec<usize>, NavError> {
+    let instructions = line
+        .trim()
+        .chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            'L' => Ok(0),
+            'R' => Ok(1),
+            _ => Err(NavError::BadInstruction { col: i + 1, c }),
+        })
+        .collect::<Result<Vec<usize>, NavError>>()?;
+    if instructions.is_empty() {
+        return Err(NavError::NoInstructions);
+    }
+    Ok(instructions)
+}
+
+fn parse_node_map(section: &str, re: &Regex) -> HashMap<String, [String; 2]> {
+    let mut node_map: HashMap<String, [String; 2]> = HashMap::new();
+    for line in section.lines().filter(|l| !l.trim().is_empty()) {
+        let caps = re.captures(line).expect("Line should be a node");
+        node_map.insert(
+            caps[1].to_string(),
+            [caps[2].to_string(), caps[3].to_string()],
+        );
+    }
+    node_map
+}
+
+// Fails if a node is named as a successor but has no line of its own.
+fn parse_network(section: &str, re: &Regex) -> Result<Network, NavError> {
+    let mut network = Network {
+        names: vec![],
+        ids: HashMap::new(),
+        next: vec![],
+    };
+    let mut defined: Vec<bool> = vec![];
+    for line in section.lines().filter(|l| !l.trim().is_empty()) {
+        let caps = re.captures(line).expect("Line should be a node");
+        let ids: Vec<u32> = (1..=3).map(|i| network.intern(&caps[i])).collect();
+        network.next[ids[0] as usize] = [ids[1], ids[2]];
+        defined.resize(network.names.len(), false);
+        defined[ids[0] as usize] = true;
+    }
+
+    let undefined: Vec<String> = network
+        .names
+        .iter()
+        .zip(&defined)
+        .filter(|(_, defined)| !**defined)
+        .map(|(name, _)| name.clone())
+        .collect();
+    if !undefined.is_empty() {
+        return Err(NavError::Undefined(undefined));
+    }
+    Ok(network)
+}
+
+fn walk_node_map(node_map: &HashMap<String, [String; 2]>, instructions: &[usize]) -> usize {
+    let mut steps: usize = 0;
+    let mut pos: String = "AAA".to_string();
+    loop {
+        let dir = instructions[steps % instructions.len()];
+        pos = node_map.get(&pos).expect("Node should exist")[dir].to_string();
+        steps += 1;
+        if pos == "ZZZ" {
+            return steps;
+        }
+    }
+}
+
+// Plain walk from AAA to ZZZ for the benchmark. It doesn't check for cycles,
+// so only call it once `navigate` has shown ZZZ is reachable.
+fn walk(network: &Network, instructions: &[usize]) -> usize {
+    let goal = network.id("ZZZ");
+    let mut steps: usize = 0;
+    let mut pos = network.id("AAA");
+    loop {
+        pos = network.step(pos, instructions[steps % instructions.len()]);
+        steps += 1;
+        if pos == goal {
+            return steps;
+        }
+    }
+}
+
+// The walk from a start node is fully determined by (node, instruction index),
+// so it always ends up in a cycle. Steps are counted from the start node.
+#[derive(Debug)]
+struct Cycle {
+    start: usize,
+    length: usize,
+    // Steps before `start + length` on which a goal node is reached, and the
+    // goal node reached on each of them.
+    goal_offsets: Vec<usize>,
+    goal_nodes: Vec<u32>,
+}
+
+impl Cycle {
+    fn pre_cycle_hits(&self) -> impl Iterator<Item = usize> + '_ {
+        self.goal_offsets
+            .iter()
+            .copied()
+            .filter(|&o| o < self.start)
+    }
+
+    fn cycle_hits(&self) -> impl Iterator<Item = usize> + '_ {
+        self.goal_offsets
+            .iter()
+            .copied()
+            .filter(|&o| o >= self.start)
+    }
+
+    // Standing on a goal at step 0 doesn't count, since no step was taken.
+    fn reaches_goal(&self) -> bool {
+        self.cycle_hits().next().is_some() || self.pre_cycle_hits().any(|o| o > 0)
+    }
+
+    fn is_hit(&self, step: usize) -> bool {
+        let offset = if step < self.start {
+            step
+        } else {
+            self.start + (step - self.start) % self.length
+        };
+        self.goal_offsets.binary_search(&offset).is_ok()
+    }
+
+    // The LCM shortcut is only valid when the walker is on a goal node exactly
+    // at the multiples of its cycle length and nowhere else. A single hit at
+    // `k * length` with k > 1 isn't enough, the first multiple may still be
+    // before the cycle starts.
+    fn is_aligned(&self) -> bool {
+        self.pre_cycle_hits().next().is_none() && self.cycle_hits().eq([self.length])
+    }
+}
+
+fn find_cycle(
+    start_node: u32,
+    network: &Network,
+    is_goal: &[bool],
+    instructions: &[usize],
+) -> Cycle {
+    // Step at which each (node, instruction index) state was first seen.
+    let mut seen: Vec<usize> = vec![usize::MAX; network.names.len() * instructions.len()];
+    let mut goal_offsets: Vec<usize> = vec![];
+    let mut goal_nodes: Vec<u32> = vec![];
+    let mut node = start_node;
+    let mut steps: usize = 0;
+
+    loop {
+        let state = node as usize * instructions.len() + steps % instructions.len();
+        if seen[state] != usize::MAX {
+            return Cycle {
+                start: seen[state],
+                length: steps - seen[state],
+                goal_offsets,
+                goal_nodes,
+            };
+        }
+        seen[state] = steps;
+
+        if is_goal[node as usize] {
+            goal_offsets.push(steps);
+            goal_nodes.push(node);
+        }
+
+        node = network.step(node, instructions[steps % instructions.len()]);
+        steps += 1;
+    }
+}
+
+// Combines t = a1 (mod m1) and t = a2 (mod m2) for moduli that need not be
+// coprime.
+fn crt(a1: i128, m1: i128, a2: i128, m2: i128) -> Option<(i128, i128)> {
+    let gcd = m1.extended_gcd(&m2);
+    if (a2 - a1) % gcd.gcd != 0 {
+        return None;
+    }
+    let m = m1 / gcd.gcd * m2;
+    let k = ((a2 - a1) / gcd.gcd * gcd.x).mod_floor(&(m2 / gcd.gcd));
+    Some(((a1 + m1 * k).mod_floor(&m), m))
+}
+
+// First step on which every walker stands on a goal node at the same time.
+fn solve(cycles: &[Cycle]) -> Option<usize> {
+    if cycles.iter().all(Cycle::is_aligned) {
+        return Some(cycles.iter().fold(1, |a, c| lcm(a, c.length)));
+    }
+
+    let mut best: Option<usize> = cycles
+        .iter()
+        .flat_map(|c| c.pre_cycle_hits())
+        .filter(|&t| t > 0 && cycles.iter().all(|c| c.is_hit(t)))
+        .min();
+
+    let mut residues: Vec<(i128, i128, usize)> = vec![(0, 1, 1)];
+    for cycle in cycles {
+        let length = cycle.length as i128;
+        residues = residues
+            .iter()
+            .flat_map(|&(a, m, bound)| {
+                cycle.cycle_hits().filter_map(move |o| {
+                    crt(a, m, o as i128, length).map(|(a, m)| (a, m, bound.max(o)))
+                })
+            })
+            .collect();
+    }
+
+    for (a, m, bound) in residues {
+        // Smallest t = a (mod m) that is past every pre-cycle section.
+        let bound = bound as i128;
+        let t = a + Integer::div_ceil(&(bound - a), &m).max(0) * m;
+        if let Ok(t) = usize::try_from(t) {
+            best = Some(best.map_or(t, |b| b.min(t)));
+        }
+    }
+    best
+}
+
+// Walks from every node matching `start` at once and returns the first step
+// on which all of them are on nodes matching `goal`.
+fn navigate(
+    network: &Network,
+    instructions: &[usize],
+    start: &str,
+    goal: &str,
+    report: bool,
+) -> Result<usize, NavError> {
+    let start_nodes = network.matching(start);
+    if start_nodes.is_empty() {
+        return Err(NavError::NoStart(start.to_string()));
+    }
+    let mut is_goal = vec![false; network.names.len()];
+    for id in network.matching(goal) {
+        is_goal[id as usize] = true;
+    }
+
+    let cycles: Vec<Cycle> = start_nodes
+        .iter()
+        .map(|&start_node| find_cycle(start_node, network, &is_goal, instructions))
+        .collect();
+
+    if report {
+        for (start_node, cycle) in start_nodes.iter().zip(&cycles) {
+            println!(
+                "{}: cycle starts at step {}, length {}, goals at {:?}{}",
+                network.names[*start_node as usize],
+                cycle.start,
+                cycle.length,
+                cycle.goal_offsets,
+                if cycle.is_aligned() {
+                    ""
+                } else {
+                    " (not aligned)"
+                }
+            );
+        }
+    }
+    if let Some((start_node, _)) = start_nodes
+        .iter()
+        .zip(&cycles)
+        .find(|(_, c)| !c.reaches_goal())
+    {
+        return Err(NavError::Unreachable {
+            start: network.names[*start_node as usize].clone(),
+            goal: goal.to_string(),
+        });
+    }
+    solve(&cycles).ok_or_else(|| NavError::NeverTogether {
+        start: start.to_string(),
+        goal: goal.to_string(),
+    })
+}
+
+fn to_dot(network: &Network) -> String {
+    let quote = |id: u32| format!("{:?}", network.names[id as usize]);
+    let mut dot = String::from("digraph network {\n");
+    for (id, &[left, right]) in network.next.iter().enumerate() {
+        let id = id as u32;
+        if left == right {
+            dot += &format!("    {} -> {} [label=\"L,R\"];\n", quote(id), quote(left));
+        } else {
+            dot += &format!("    {} -> {} [label=\"L\"];\n", quote(id), quote(left));
+            dot += &format!("    {} -> {} [label=\"R\"];\n", quote(id), quote(right));
+        }
+    }
+    dot += "}";
+    dot
+}
+
+// Nodes reachable from any of `starts` following either successor, ignoring
+// the instruction order.
+fn reachable_from(network: &Network, starts: &[u32]) -> Vec<bool> {
+    let mut reached = vec![false; network.names.len()];
+    let mut stack: Vec<u32> = starts.to_vec();
+    while let Some(node) = stack.pop() {
+        if reached[node as usize] {
+            continue;
+        }
+        reached[node as usize] = true;
+        stack.extend(network.next[node as usize]);
+    }
+    reached
+}
+
+// Kosaraju's algorithm with explicit stacks: finish order on the network,
+// then components by walking the reversed edges in reverse finish order.
+fn strongly_connected(network: &Network) -> Vec<Vec<u32>> {
+    let n = network.names.len();
+    let mut order: Vec<u32> = Vec::with_capacity(n);
+    let mut visited = vec![false; n];
+    for root in 0..n as u32 {
+        if visited[root as usize] {
+            continue;
+        }
+        visited[root as usize] = true;
+        let mut stack: Vec<(u32, usize)> = vec![(root, 0)];
+        while let Some((node, child)) = stack.pop() {
+            if child == 2 {
+                order.push(node);
+                continue;
+            }
+            stack.push((node, child + 1));
+            let next = network.next[node as usize][child];
+            if !visited[next as usize] {
+                visited[next as usize] = true;
+                stack.push((next, 0));
+            }
+        }
+    }
+
+    let mut reverse: Vec<Vec<u32>> = vec![vec![]; n];
+    for (id, successors) in network.next.iter().enumerate() {
+        for &next in successors {
+            reverse[next as usize].push(id as u32);
+        }
+    }
+
+    let mut component = vec![usize::MAX; n];
+    let mut components: Vec<Vec<u32>> = vec![];
+    for &root in order.iter().rev() {
+        if component[root as usize] != usize::MAX {
+            continue;
+        }
+        let mut members: Vec<u32> = vec![];
+        let mut stack = vec![root];
+        component[root as usize] = components.len();
+        while let Some(node) = stack.pop() {
+            members.push(node);
+            for &prev in &reverse[node as usize] {
+                if component[prev as usize] == usize::MAX {
+                    component[prev as usize] = components.len();
+                    stack.push(prev);
+                }
+            }
+        }
+        components.push(members);
+    }
+    components
+}
+
+fn reachability_report(network: &Network, instructions: &[usize], start: &str, goal: &str) {
+    let names = |ids: &[u32]| {
+        let mut names: Vec<&str> = ids
+            .iter()
+            .map(|&id| network.names[id as usize].as_str())
+            .collect();
+        names.sort();
+        names.join(", ")
+    };
+
+    let components = strongly_connected(network);
+    let cyclic: Vec<&Vec<u32>> = components
+        .iter()
+        .filter(|c| c.len() > 1 || network.next[c[0] as usize].contains(&c[0]))
+        .collect();
+    println!(
+        "{} strongly connected components, {} with a cycle",
+        components.len(),
+        cyclic.len()
+    );
+    for members in cyclic {
+        println!("  {} nodes: {}", members.len(), names(members));
+    }
+
+    let start_nodes = network.matching(start);
+    let reached = reachable_from(network, &start_nodes);
+    let unreachable: Vec<u32> = (0..network.names.len() as u32)
+        .filter(|&id| !reached[id as usize])
+        .collect();
+    if unreachable.is_empty() {
+        println!("Every node is reachable from {} by some path", start);
+    } else {
+        println!(
+            "{} nodes unreachable from {} by any path: {}",
+            unreachable.len(),
+            start,
+            names(&unreachable)
+        );
+    }
+
+    // Graph reachability follows either successor at every step, the walk
+    // only the ones the instructions pick, so it can reach fewer goals.
+    let mut is_goal = vec![false; network.names.len()];
+    for id in network.matching(goal) {
+        is_goal[id as usize] = true;
+    }
+    let list = |ids: &[u32]| {
+        if ids.is_empty() {
+            "no goal".to_string()
+        } else {
+            names(ids)
+        }
+    };
+    for &start_node in &start_nodes {
+        let reached = reachable_from(network, &[start_node]);
+        let graph_goals: Vec<u32> = (0..network.names.len() as u32)
+            .filter(|&id| is_goal[id as usize] && reached[id as usize])
+            .collect();
+        let mut walk_goals = find_cycle(start_node, network, &is_goal, instructions).goal_nodes;
+        walk_goals.sort_unstable();
+        walk_goals.dedup();
+        println!(
+            "{} reaches {} following the instructions (graph-reachable: {})",
+            network.names[start_node as usize],
+            list(&walk_goals),
+            list(&graph_goals)
+        );
+    }
+}
+
+fn exit_with(e: NavError) -> ! {
+    eprintln!("{}", e);
+    process::exit(1);
+}
+
+fn bench(section: &str, re: &Regex, instructions: &[usize]) {
+    const RUNS: usize = 100;
+
+    let start = Instant::now();
+    let node_map = parse_node_map(section, re);
+    println!("string map parse: {:?}", start.elapsed());
+    let start = Instant::now();
+    let network = parse_network(section, re).unwrap_or_else(|e| exit_with(e));
+    println!("interned parse: {:?}", start.elapsed());
+
+    // Neither timed walk checks for cycles, so make sure they terminate first.
+    let expected =
+        navigate(&network, instructions, "AAA", "ZZZ", false).unwrap_or_else(|e| exit_with(e));
+
+    let start = Instant::now();
+    let steps: usize = (0..RUNS)
+        .map(|_| walk_node_map(&node_map, instructions))
+        .sum();
+    let string_time = start.elapsed();
+    println!("string map walk x{}: {:?}", RUNS, string_time);
+
+    let start = Instant::now();
+    let interned_steps: usize = (0..RUNS).map(|_| walk(&network, instructions)).sum();
+    let interned_time = start.elapsed();
+    println!("interned walk x{}: {:?}", RUNS, interned_time);
+
+    assert_eq!(steps, interned_steps);
+    assert_eq!(steps, expected * RUNS);
+    println!(
+        "speedup: {:.1}x",
+        string_time.as_secs_f64() / interned_time.as_secs_f64()
+    );
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let arg_value = |flag: &str| {
+        args.iter()
+            .position(|a| a == flag)
+            .and_then(|i| args.get(i + 1))
+            .cloned()
+    };
+    let report = args.iter().any(|a| a == "--cycles");
+    let re = Regex::new(NODE_LINE).unwrap();
+
+    let input = fs::read_to_string("input.txt").expect("File should open");
+    let (instruction_line, nodes) = input
+        .split_once("\n\n")
+        .unwrap_or_else(|| exit_with(NavError::NoNodes));
+    let instructions = parse_instructions(instruction_line).unwrap_or_else(|e| exit_with(e));
+
+    if args.iter().any(|a| a == "--bench") {
+        bench(nodes, &re, &instructions);
+        return;
+    }
+
+    let network = parse_network(nodes, &re).unwrap_or_else(|e| exit_with(e));
+    if args.iter().any(|a| a == "--dot") {
+        println!("{}", to_dot(&network));
+        return;
+    }
+    if args.iter().any(|a| a == "--reachability") {
+        let start = arg_value("--start").unwrap_or("*A".to_string());
+        let goal = arg_value("--goal").unwrap_or("*Z".to_string());
+        reachability_report(&network, &instructions, &start, &goal);
+        return;
+    }
+
+    let queries: Vec<(String, String, String)> = match (arg_value("--start"), arg_value("--goal")) {
+        (None, None) => vec![
+            ("Part 1".to_string(), "AAA".to_string(), "ZZZ".to_string()),
+            ("Part 2".to_string(), "*A".to_string(), "*Z".to_string()),
+        ],
+        (start, goal) => {
+            let start = start.unwrap_or("AAA".to_string());
+            let goal = goal.unwrap_or("ZZZ".to_string());
+            vec![(format!("{} -> {}", start, goal), start, goal)]
+        }
+    };
+
+    // Answers go to stdout and failures to stderr, with a failing exit code
+    // once every query has been tried.
+    let mut failed = false;
+    for (label, start, goal) in queries {
+        match navigate(&network, &instructions, &start, &goal, report) {
+            Ok(steps) => println!("{}: {}", label, steps),
+            Err(e) => {
+                eprintln!("{}: {}", label, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> (Vec<usize>, Network) {
+        let re = Regex::new(NODE_LINE).unwrap();
+        let (instructions, nodes) = input.split_once("\n\n").unwrap();
+        (
+            parse_instructions(instructions).unwrap(),
+            parse_network(nodes, &re).unwrap(),
+        )
+    }
+
+    #[test]
+    fn goal_before_late_cycle() {
+        // The only goal hit is at step 6 = 2 * cycle length, with the cycle
+        // starting at step 4, so the LCM shortcut would answer 3.
+        let (instructions, network) = parse(
+            "L\n\nAAA = (BBB, BBB)\nBBB = (CCC, CCC)\nCCC = (DDD, DDD)\nDDD = (EEE, EEE)\n\
+             EEE = (FFF, FFF)\nFFF = (ZZZ, ZZZ)\nZZZ = (EEE, EEE)",
+        );
+        assert_eq!(
+            navigate(&network, &instructions, "AAA", "ZZZ", false).unwrap(),
+            6
+        );
+        assert_eq!(
+            navigate(&network, &instructions, "*A", "*Z", false).unwrap(),
+            6
+        );
+    }
+
+    #[test]
+    fn puzzle_examples() {
+        let (instructions, network) =
+            parse("LLR\n\nAAA = (BBB, BBB)\nBBB = (AAA, ZZZ)\nZZZ = (ZZZ, ZZZ)");
+        assert_eq!(
+            navigate(&network, &instructions, "AAA", "ZZZ", false).unwrap(),
+            6
+        );
+
+        let (instructions, network) = parse(
+            "LR\n\n11A = (11B, XXX)\n11B = (XXX, 11Z)\n11Z = (11B, XXX)\n22A = (22B, XXX)\n\
+             22B = (22C, 22C)\n22C = (22Z, 22Z)\n22Z = (22B, 22B)\nXXX = (XXX, XXX)",
+        );
+        assert_eq!(
+            navigate(&network, &instructions, "*A", "*Z", false).unwrap(),
+            6
+        );
+    }
+
+    #[test]
+    fn unreachable_goal() {
+        let (instructions, network) =
+            parse("LL\n\nAAA = (BBB, ZZZ)\nBBB = (AAA, ZZZ)\nZZZ = (ZZZ, ZZZ)");
+        assert!(matches!(
+            navigate(&network, &instructions, "AAA", "ZZZ", false),
+            Err(NavError::Unreachable { .. })
+        ));
+
+        // Starting on the goal is step 0, which doesn't count as reaching it.
+        let (instructions, network) =
+            parse("L\n\nZZZ = (AAA, AAA)\nAAA = (BBB, BBB)\nBBB = (AAA, AAA)");
+        assert!(matches!(
+            navigate(&network, &instructions, "ZZZ", "ZZZ", false),
+            Err(NavError::Unreachable { .. })
+        ));
+    }
+
+    #[test]
+    fn undefined_nodes() {
+        let re = Regex::new(NODE_LINE).unwrap();
+        let Err(NavError::Undefined(names)) =
+            parse_network("AAA = (BBB, QQQ)\nBBB = (ZZZ, RRR)\nZZZ = (ZZZ, ZZZ)", &re)
+        else {
+            panic!("Undefined nodes should be reported");
+        };
+        assert_eq!(names, ["QQQ", "RRR"]);
+    }
+
+    #[test]
+    fn bad_instruction() {
+        assert!(matches!(
+            parse_instructions("LRX"),
+            Err(NavError::BadInstruction { col: 3, c: 'X' })
+        ));
+    }
+}
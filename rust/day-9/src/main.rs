@@ -1,74 +1,409 @@
-use std::{fs, vec};
+use num::{BigInt, BigRational, One, Signed, Zero};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::{env, fmt, process, vec};
 
-fn add_rows(values: &mut Vec<Vec<i32>>) {
+#[derive(Debug)]
+struct OverflowError;
+
+impl fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "extrapolation overflows i64")
+    }
+}
+
+fn add_rows(values: &mut Vec<Vec<i64>>) -> Result<(), OverflowError> {
     while !values[values.len() - 1].iter().all(|&n| n == 0) {
-        let mut new_values: Vec<i32> = vec![];
+        let mut new_values: Vec<i64> = vec![];
 
         for i in 0..values[values.len() - 1].len() - 1 {
-            let diff = values[values.len() - 1][i + 1] - values[values.len() - 1][i];
+            let diff = values[values.len() - 1][i + 1]
+                .checked_sub(values[values.len() - 1][i])
+                .ok_or(OverflowError)?;
             new_values.push(diff);
         }
         values.push(new_values);
     }
+    Ok(())
 }
 
-fn get_extrapolated_left(nums: &Vec<i32>) -> i32 {
-    let mut values = vec![nums.clone()];
-    add_rows(&mut values);
-    fill_placeholders_left(&mut values);
-    *values
-        .first()
-        .expect("Should have rows")
-        .first()
-        .expect("Should have a value")
+// Both ends come from the binomial form first. Only when its i128 sums
+// overflow is the difference pyramid built, walking back up it with checked
+// arithmetic.
+fn get_extrapolated_left(nums: &[i64]) -> Result<i64, OverflowError> {
+    if let Some(v) = extrapolate_behind(nums, 1) {
+        return Ok(v);
+    }
+    let mut values = vec![nums.to_vec()];
+    add_rows(&mut values)?;
+    values.iter().rev().try_fold(0i64, |below, row| {
+        row.first()
+            .unwrap_or(&0)
+            .checked_sub(below)
+            .ok_or(OverflowError)
+    })
 }
 
-fn get_extrapolated_right(nums: &Vec<i32>) -> i32 {
-    let mut values = vec![nums.clone()];
-    add_rows(&mut values);
-    fill_placeholders_right(&mut values);
-    *values
-        .first()
-        .expect("Should have rows")
+fn get_extrapolated_right(nums: &[i64]) -> Result<i64, OverflowError> {
+    if let Some(v) = extrapolate_ahead(nums, 1) {
+        return Ok(v);
+    }
+    let mut values = vec![nums.to_vec()];
+    add_rows(&mut values)?;
+    values.iter().rev().try_fold(0i64, |below, row| {
+        row.last()
+            .unwrap_or(&0)
+            .checked_add(below)
+            .ok_or(OverflowError)
+    })
+}
+
+// Generalised binomial coefficient C(m, k), defined for negative `m` too.
+// Each partial product is itself a binomial coefficient, so the division is
+// always exact. Returns None on overflow.
+fn binomial(m: i128, k: usize) -> Option<i128> {
+    let mut result: i128 = 1;
+    for j in 0..k as i128 {
+        result = result.checked_mul(m - j)? / (j + 1);
+    }
+    Some(result)
+}
+
+// Value at position `t` of the lowest degree polynomial through
+// (0, nums[0]), ..., (n - 1, nums[n - 1]), from the Lagrange form
+// L_i(t) = (-1)^(n-1-i) * C(t, i) * C(t - i - 1, n - 1 - i).
+// This is what the difference pyramid gives, without building it.
+fn extrapolate(nums: &[i64], t: i64) -> Option<i64> {
+    let n = nums.len();
+    let t = t as i128;
+    let mut total: i128 = 0;
+    for (i, &v) in nums.iter().enumerate() {
+        let sign = if (n - 1 - i).is_multiple_of(2) { 1 } else { -1 };
+        let weight = binomial(t, i)?.checked_mul(binomial(t - i as i128 - 1, n - 1 - i)?)?;
+        total = total.checked_add(weight.checked_mul(sign * v as i128)?)?;
+    }
+    i64::try_from(total).ok()
+}
+
+// The value `k` steps after the last one.
+fn extrapolate_ahead(nums: &[i64], k: i64) -> Option<i64> {
+    extrapolate(nums, nums.len() as i64 - 1 + k)
+}
+
+// The value `k` steps before the first one.
+fn extrapolate_behind(nums: &[i64], k: i64) -> Option<i64> {
+    extrapolate(nums, -k)
+}
+
+// Forward differences of `nums` down to the first row of zeros, in big
+// integers so nothing can overflow. A row that runs out of values counts as
+// zeros, like in `add_rows`.
+fn difference_rows(nums: &[i64]) -> Vec<Vec<BigInt>> {
+    let mut rows: Vec<Vec<BigInt>> = vec![nums.iter().map(|&n| BigInt::from(n)).collect()];
+    while !rows
         .last()
-        .expect("Should have a value")
+        .expect("Should have rows")
+        .iter()
+        .all(|n| n.is_zero())
+    {
+        let last = rows.last().expect("Should have rows");
+        let next = last.windows(2).map(|w| &w[1] - &w[0]).collect();
+        rows.push(next);
+    }
+    rows
+}
+
+// The degree of the polynomial is the number of rows above the zeros, less
+// one. When the values ran out before a real row of zeros there's no way to
+// tell the degree.
+fn reaches_zero(rows: &[Vec<BigInt>]) -> bool {
+    !rows.last().expect("Should have rows").is_empty()
 }
 
-fn fill_placeholders_left(nums: &mut Vec<Vec<i32>>) {
-    nums.last_mut().expect("Should have a vector").insert(0, 0);
-    for i in (0..nums.len() - 1).rev() {
-        let new_v = nums[i].first().expect("Should have elements")
-            - nums[i + 1].first().expect("Should have elements");
-        nums[i].insert(0, new_v);
+// The next and previous values from big integer difference rows.
+fn extrapolate_big(rows: &[Vec<BigInt>]) -> (BigInt, BigInt) {
+    let mut right = BigInt::zero();
+    let mut left = BigInt::zero();
+    for row in rows.iter().rev() {
+        if let (Some(first), Some(last)) = (row.first(), row.last()) {
+            right = last + right;
+            left = first - left;
+        }
     }
+    (right, left)
 }
 
-fn fill_placeholders_right(nums: &mut Vec<Vec<i32>>) {
-    nums.last_mut().expect("Should have a vector").push(0);
+// Coefficients of the fitted polynomial, constant term first, with t = 0 at
+// the first value. Built from Newton's forward difference formula,
+// p(t) = sum of (first value of row j) * t(t-1)...(t-j+1) / j!.
+fn fit_polynomial(rows: &[Vec<BigInt>]) -> Vec<BigRational> {
+    let degree = rows.len().saturating_sub(2);
+    let mut coefficients = vec![BigRational::zero(); degree + 1];
+    let mut falling = vec![BigRational::one()];
+    let mut factorial = BigInt::one();
+
+    for (j, row) in rows.iter().take(degree + 1).enumerate() {
+        if j > 0 {
+            factorial *= j;
+            // falling *= (t - (j - 1))
+            let shift = BigRational::from_integer(BigInt::from(j - 1));
+            let mut next = vec![BigRational::zero(); falling.len() + 1];
+            for (k, c) in falling.iter().enumerate() {
+                next[k + 1] += c;
+                next[k] -= c * &shift;
+            }
+            falling = next;
+        }
+        let scale = BigRational::new(row[0].clone(), factorial.clone());
+        for (k, c) in falling.iter().enumerate() {
+            coefficients[k] += c * &scale;
+        }
+    }
+    coefficients
+}
 
-    for i in (0..nums.len() - 1).rev() {
-        let new_v = nums[i].last().expect("Should have elements")
-            + nums[i + 1].last().expect("Should have elements");
-        nums[i].push(new_v);
+fn format_polynomial(coefficients: &[BigRational]) -> String {
+    let terms: Vec<String> = coefficients
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, c)| !c.is_zero())
+        .map(|(k, c)| {
+            // A coefficient of 1 or -1 reads as just the sign on a power of t.
+            let c = if k > 0 && c.abs().is_one() {
+                if c.is_negative() { "-" } else { "" }.to_string()
+            } else {
+                format!("{} ", c)
+            };
+            match k {
+                0 => c.trim_end().to_string(),
+                1 => format!("{}t", c),
+                _ => format!("{}t^{}", c, k),
+            }
+        })
+        .collect();
+    if terms.is_empty() {
+        "0".to_string()
+    } else {
+        terms.join(" + ").replace("+ -", "- ")
     }
 }
 
 fn main() {
-    let (mut total_1, mut total_2) = (0, 0);
+    let args: Vec<String> = env::args().collect();
+    let steps: Option<i64> = args.iter().position(|a| a == "--steps").map(|i| {
+        args.get(i + 1)
+            .and_then(|k| k.parse().ok())
+            .unwrap_or_else(|| {
+                eprintln!("--steps needs a whole number of steps");
+                eprintln!("Usage: day-9 [--steps K] [--fit] [--big] [--stream [FILE]]");
+                process::exit(1);
+            })
+    });
+    let fit = args.iter().any(|a| a == "--fit");
+    let big = args.iter().any(|a| a == "--big");
+    // `--stream [FILE]` reads FILE (or stdin when missing or `-`) line by line
+    // and prints the running totals after every history.
+    let stream = args.iter().position(|a| a == "--stream").map(|i| {
+        args.get(i + 1)
+            .filter(|p| !p.starts_with("--"))
+            .map_or("-", |p| p.as_str())
+    });
+    let reader: Box<dyn BufRead> = match stream {
+        Some("-") => Box::new(io::stdin().lock()),
+        Some(path) => Box::new(BufReader::new(File::open(path).expect("File should open"))),
+        None => Box::new(BufReader::new(
+            File::open("input.txt").expect("File should open"),
+        )),
+    };
+    let (mut total_ahead, mut total_behind) = (Some(0i64), Some(0i64));
 
-    for line in fs::read_to_string("input.txt")
-        .expect("File should open")
-        .lines()
-    {
-        let nums: Vec<i32> = line
-            .split(" ")
+    let (mut total_1, mut total_2) = (BigInt::zero(), BigInt::zero());
+
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line.expect("Line should be readable");
+        if line.trim().is_empty() {
+            continue;
+        }
+        let nums: Vec<i64> = line
+            .split_whitespace()
             .map(|s| s.parse().expect("Should be a number"))
             .collect();
 
-        total_1 += get_extrapolated_right(&nums);
-        total_2 += get_extrapolated_left(&nums);
+        if fit {
+            let rows = difference_rows(&nums);
+            if reaches_zero(&rows) {
+                println!(
+                    "Line {}: degree {}, p(t) = {}",
+                    line_no + 1,
+                    rows.len().saturating_sub(2),
+                    format_polynomial(&fit_polynomial(&rows))
+                );
+            } else {
+                println!(
+                    "Line {}: differences never reach zero, degree unknown",
+                    line_no + 1
+                );
+            }
+        }
+
+        match (get_extrapolated_right(&nums), get_extrapolated_left(&nums)) {
+            (Ok(right), Ok(left)) => {
+                total_1 += right;
+                total_2 += left;
+            }
+            _ if big => {
+                let (right, left) = extrapolate_big(&difference_rows(&nums));
+                total_1 += right;
+                total_2 += left;
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("Line {}: {}, rerun with --big", line_no + 1, e);
+                process::exit(1);
+            }
+        }
+
+        if stream.is_some() {
+            println!(
+                "Line {}: running totals {} / {}",
+                line_no + 1,
+                total_1,
+                total_2
+            );
+        }
+
+        if let Some(k) = steps {
+            total_ahead = total_ahead
+                .zip(extrapolate_ahead(&nums, k))
+                .and_then(|(a, b)| a.checked_add(b));
+            total_behind = total_behind
+                .zip(extrapolate_behind(&nums, k))
+                .and_then(|(a, b)| a.checked_add(b));
+        }
     }
 
     println!("Part 1: {}", total_1);
     println!("Part 2: {}", total_2);
+
+    if let Some(k) = steps {
+        let show = |total: Option<i64>| total.map_or("overflow".to_string(), |t| t.to_string());
+        println!("{} steps ahead: {}", k, show(total_ahead));
+        println!("{} steps behind: {}", k, show(total_behind));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn puzzle_example() {
+        let histories: [&[i64]; 3] = [
+            &[0, 3, 6, 9, 12, 15],
+            &[1, 3, 6, 10, 15, 21],
+            &[10, 13, 16, 21, 30, 45],
+        ];
+        let right: Vec<i64> = histories
+            .iter()
+            .map(|h| get_extrapolated_right(h).unwrap())
+            .collect();
+        let left: Vec<i64> = histories
+            .iter()
+            .map(|h| get_extrapolated_left(h).unwrap())
+            .collect();
+        assert_eq!(right, [18, 28, 68]);
+        assert_eq!(left, [-3, 0, 5]);
+    }
+
+    // Long, steep sequences checked against the big integer pyramid: powers
+    // (t + 1)^k and alternating +-scale, whose differences double every row. The
+    // i64 methods must either agree or report overflow, and only report it when
+    // some value really is out of range.
+    #[test]
+    fn steep_sequences() {
+        let mut sequences: Vec<Vec<i64>> = vec![];
+        for len in [10, 20, 40, 60, 80] {
+            for power in 0..len as u32 {
+                if let Some(nums) = (0..len as i64)
+                    .map(|t| (t + 1).checked_pow(power))
+                    .collect()
+                {
+                    sequences.push(nums);
+                }
+            }
+            for scale in [1, 1_000, 1_000_000_000_000, i64::MAX / 2, i64::MAX] {
+                sequences.push(
+                    (0..len)
+                        .map(|t| if t % 2 == 0 { scale } else { -scale })
+                        .collect(),
+                );
+            }
+        }
+
+        for nums in &sequences {
+            let rows = difference_rows(nums);
+            let (right, left) = extrapolate_big(&rows);
+            let fits = |n: &BigInt| i64::try_from(n).is_ok();
+            let all_fit = rows.iter().flatten().all(fits) && fits(&right) && fits(&left);
+
+            for (result, expected) in [
+                (get_extrapolated_right(nums), &right),
+                (get_extrapolated_left(nums), &left),
+            ] {
+                match result {
+                    Ok(v) => assert_eq!(&BigInt::from(v), expected, "{:?}", nums),
+                    Err(_) => assert!(!all_fit, "False overflow for {:?}", nums),
+                }
+            }
+            if let Some(v) = extrapolate_ahead(nums, 1) {
+                assert_eq!(BigInt::from(v), right, "{:?}", nums);
+            }
+            if let Some(v) = extrapolate_behind(nums, 1) {
+                assert_eq!(BigInt::from(v), left, "{:?}", nums);
+            }
+        }
+    }
+
+    #[test]
+    fn example_polynomials() {
+        let histories: [&[i64]; 3] = [
+            &[0, 3, 6, 9, 12, 15],
+            &[1, 3, 6, 10, 15, 21],
+            &[10, 13, 16, 21, 30, 45],
+        ];
+        let fitted: Vec<String> = histories
+            .iter()
+            .map(|h| {
+                let rows = difference_rows(h);
+                assert!(reaches_zero(&rows), "{:?}", h);
+                format_polynomial(&fit_polynomial(&rows))
+            })
+            .collect();
+        assert_eq!(
+            fitted,
+            ["3 t", "1/2 t^2 + 3/2 t + 1", "1/3 t^3 - t^2 + 11/3 t + 10"]
+        );
+    }
+
+    #[test]
+    fn degenerate_polynomials() {
+        assert!(!reaches_zero(&difference_rows(&[1, 2, 4])));
+        assert!(!reaches_zero(&difference_rows(&[5])));
+
+        let rows = difference_rows(&[0]);
+        assert!(reaches_zero(&rows));
+        assert_eq!(rows.len().saturating_sub(2), 0);
+        assert_eq!(format_polynomial(&fit_polynomial(&rows)), "0");
+    }
+
+    #[test]
+    fn several_steps() {
+        let nums = [0, 3, 6, 9, 12, 15];
+        assert_eq!(extrapolate_ahead(&nums, 3), Some(24));
+        assert_eq!(extrapolate_behind(&nums, 3), Some(-9));
+
+        let nums = [10, 13, 16, 21, 30, 45];
+        assert_eq!(extrapolate_ahead(&nums, 2), Some(101));
+        assert_eq!(extrapolate_behind(&nums, 2), Some(-4));
+    }
 }